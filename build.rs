@@ -4,15 +4,45 @@
 
 // Import necessary modules from the standard library.
 use std::env; // For accessing environment variables like OUT_DIR.
-use std::path::PathBuf; // For working with file paths in a platform-agnostic way.
+use std::fmt::Write as _; // For `write!`/`writeln!` into the generated source `String`.
+use std::fs; // For reading the register map and writing the generated code.
+use std::path::{Path, PathBuf}; // For working with file paths in a platform-agnostic way.
 use std::process::Command; // For running external commands like the assembler.
 
+// Declarative description of all peripheral registers (see the header of the file for the syntax).
+const REGISTER_MAP: &str = "src/hal/registers/peripherals.regmap";
+
 fn main() {
     // Get the path to the output directory for this build.
     // Cargo sets the OUT_DIR environment variable to a directory inside `target`
     // where build scripts can place their output.
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
-    
+
+    // Generate the `hal::registers::*` modules from the register map.
+    // The result is `include!`d by `src/hal/registers/mod.rs`.
+    let map = fs::read_to_string(REGISTER_MAP).expect("Failed to read the register map");
+    let generated = generate_registers(&parse_register_map(&map));
+    fs::write(out_dir.join("registers.rs"), generated).expect("Failed to write generated registers");
+
+    // The boot code is only needed for the kernel itself. Host builds (`make test`, which runs the
+    // unit tests on the development machine) have no use for it and no AArch64 assembler.
+    if env::var("CARGO_CFG_TARGET_ARCH").unwrap() == "aarch64" {
+        assemble_boot_code(&out_dir);
+    }
+
+    // Tell Cargo to re-run this build script if `src/boot.S` changes.
+    // This ensures that if the assembly source is modified, it gets recompiled.
+    println!("cargo:rerun-if-changed=src/boot.S");
+    // Tell Cargo to re-run this build script if `linker.ld` (the linker script) changes.
+    // This is important because linker scripts define how the final executable is laid out in memory,
+    // and changes to it might require a full rebuild or relinking.
+    println!("cargo:rerun-if-changed=linker.ld");
+    // Regenerate the register modules whenever the register map changes.
+    println!("cargo:rerun-if-changed={}", REGISTER_MAP);
+}
+
+// Assemble `src/boot.S` and link the resulting object into the kernel.
+fn assemble_boot_code(out_dir: &Path) {
     // Assemble the `src/boot.S` assembly file.
     // This section invokes an external assembler (`aarch64-linux-gnu-as`)
     // to compile the assembly code into an object file (`boot.o`).
@@ -42,12 +72,362 @@ fn main() {
         "cargo:rustc-link-arg={}",
         out_dir.join("boot.o").display() // The path to the object file.
     );
-    
-    // Tell Cargo to re-run this build script if `src/boot.S` changes.
-    // This ensures that if the assembly source is modified, it gets recompiled.
-    println!("cargo:rerun-if-changed=src/boot.S");
-    // Tell Cargo to re-run this build script if `linker.ld` (the linker script) changes.
-    // This is important because linker scripts define how the final executable is laid out in memory,
-    // and changes to it might require a full rebuild or relinking.
-    println!("cargo:rerun-if-changed=linker.ld");
+}
+
+// --- Register map code generator ---
+// The register map is a simple line-based format, so it is parsed by hand
+// (build scripts of this crate have no dependencies either).
+
+/// A named value of a bitfield (e.g. `WLEN::Eight`).
+#[derive(Clone)]
+struct Value {
+    docs: Vec<String>,
+    name: String,
+    value: String, // Kept as written (e.g. `0b011`) so the generated code reads like the map
+}
+
+/// A field inside a register (e.g. `LCRH::WLEN`).
+struct Field {
+    docs: Vec<String>,
+    name: String,
+    offset: u32,
+    numbits: u32,
+    values: Vec<Value>,
+}
+
+/// The bitfields of one register type (e.g. `LCRH`). Several registers can share one bitfield.
+struct Bitfield {
+    docs: Vec<String>,
+    name: String,
+    fields: Vec<Field>,
+}
+
+/// A single 32-bit register (or an array of consecutive registers) inside a block.
+struct Register {
+    docs: Vec<String>,
+    name: String,
+    offset: u64,
+    count: Option<u64>, // `Some(n)` for an array `name[n]`
+    access: &'static str, // Cell type: ReadOnly, WriteOnly or ReadWrite
+    bitfield: Option<String>,
+}
+
+/// A further instance of a register block: same struct, different offset.
+struct Instance {
+    docs: Vec<String>,
+    prefix: String,
+    offset: u64,
+}
+
+/// A register block, generated as a `#[repr(C)]` struct at `base + offset`.
+struct Block {
+    docs: Vec<String>,
+    name: String,
+    prefix: String,
+    offset: u64,
+    registers: Vec<Register>,
+    instances: Vec<Instance>,
+}
+
+/// A generated Rust module (`hal::registers::<name>`).
+struct Module {
+    name: String,
+    docs: Vec<String>,
+    bitfields: Vec<Bitfield>,
+    blocks: Vec<Block>,
+}
+
+struct RegisterMap {
+    modules: Vec<Module>,
+}
+
+impl Register {
+    /// Offset of the first byte after this register (or array).
+    fn end(&self) -> u64 {
+        self.offset + 4 * self.count.unwrap_or(1)
+    }
+}
+
+/// Parse a number written as hex (`0x`), binary (`0b`) or decimal.
+fn parse_number(text: &str) -> Option<u64> {
+    let text = text.replace('_', "");
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = text.strip_prefix("0b") {
+        u64::from_str_radix(bin, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+fn parse_register_map(text: &str) -> RegisterMap {
+    let mut modules: Vec<Module> = Vec::new();
+    let mut enums: Vec<(String, Vec<Value>)> = Vec::new();
+    let mut docs: Vec<String> = Vec::new();
+    // Which list a `value` line belongs to: the last field, or the last enum.
+    let mut values_target_is_enum = false;
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = raw_line.trim();
+        // Abort the build with the location of the problem in the map.
+        let fail = |message: &str| -> ! {
+            panic!("{}:{}: {} (`{}`)", REGISTER_MAP, index + 1, message, line)
+        };
+
+        if let Some(doc) = line.strip_prefix("//!") {
+            match modules.last_mut() {
+                Some(module) => module.docs.push(doc.to_string()),
+                None => fail("`//!` outside of a module"),
+            }
+            continue;
+        }
+        if let Some(doc) = line.strip_prefix("///") {
+            docs.push(doc.to_string());
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |position: usize| -> u64 {
+            match words.get(position).and_then(|word| parse_number(word)) {
+                Some(value) => value,
+                None => fail("expected a number"),
+            }
+        };
+        let word = |position: usize| -> String {
+            match words.get(position) {
+                Some(word) => word.to_string(),
+                None => fail("missing argument"),
+            }
+        };
+        let item_docs = std::mem::take(&mut docs);
+
+        match words[0] {
+            "module" => modules.push(Module {
+                name: word(1),
+                docs: item_docs,
+                bitfields: Vec::new(),
+                blocks: Vec::new(),
+            }),
+            "enum" => {
+                enums.push((word(1), Vec::new()));
+                values_target_is_enum = true;
+            }
+            "bitfield" => {
+                let Some(module) = modules.last_mut() else { fail("bitfield outside of a module") };
+                module.bitfields.push(Bitfield { docs: item_docs, name: word(1), fields: Vec::new() });
+            }
+            "field" => {
+                let Some(bitfield) = modules.last_mut().and_then(|m| m.bitfields.last_mut()) else {
+                    fail("field outside of a bitfield")
+                };
+                let (offset, numbits) = (number(2) as u32, number(3) as u32);
+                if numbits == 0 || offset + numbits > 32 {
+                    fail("field does not fit into a 32-bit register");
+                }
+                bitfield.fields.push(Field { docs: item_docs, name: word(1), offset, numbits, values: Vec::new() });
+                values_target_is_enum = false;
+            }
+            "value" => {
+                let value = Value { docs: item_docs, name: word(1), value: word(2) };
+                let width = number(2);
+                if values_target_is_enum {
+                    enums.last_mut().unwrap().1.push(value);
+                } else {
+                    let Some(field) = modules
+                        .last_mut()
+                        .and_then(|m| m.bitfields.last_mut())
+                        .and_then(|b| b.fields.last_mut())
+                    else {
+                        fail("value outside of a field or enum")
+                    };
+                    if width >> field.numbits != 0 {
+                        fail("value does not fit into the field");
+                    }
+                    field.values.push(value);
+                }
+            }
+            "values" => {
+                let name = word(1);
+                let Some((_, values)) = enums.iter().find(|(enum_name, _)| *enum_name == name) else {
+                    fail("unknown enum")
+                };
+                let Some(field) = modules
+                    .last_mut()
+                    .and_then(|m| m.bitfields.last_mut())
+                    .and_then(|b| b.fields.last_mut())
+                else {
+                    fail("values outside of a field")
+                };
+                field.values.extend(values.iter().cloned());
+            }
+            "block" => {
+                let Some(module) = modules.last_mut() else { fail("block outside of a module") };
+                module.blocks.push(Block {
+                    docs: item_docs,
+                    name: word(1),
+                    prefix: word(2),
+                    offset: number(3),
+                    registers: Vec::new(),
+                    instances: Vec::new(),
+                });
+            }
+            "instance" => {
+                let Some(block) = modules.last_mut().and_then(|m| m.blocks.last_mut()) else {
+                    fail("instance outside of a block")
+                };
+                block.instances.push(Instance { docs: item_docs, prefix: word(1), offset: number(2) });
+            }
+            "reg" => {
+                let Some(module) = modules.last_mut() else { fail("register outside of a module") };
+                let bitfield = words.get(4).map(|name| name.to_string());
+                if let Some(name) = &bitfield {
+                    if !module.bitfields.iter().any(|b| &b.name == name) {
+                        fail("unknown bitfield (bitfields must be declared before use)");
+                    }
+                }
+                let Some(block) = module.blocks.last_mut() else { fail("register outside of a block") };
+                let access = match words.get(3).copied() {
+                    Some("ro") => "ReadOnly",
+                    Some("wo") => "WriteOnly",
+                    Some("rw") => "ReadWrite",
+                    _ => fail("access must be one of ro, wo, rw"),
+                };
+                let offset = number(2);
+                if offset % 4 != 0 {
+                    fail("register offset must be 4-byte aligned");
+                }
+                if let Some(last) = block.registers.last() {
+                    if offset < last.end() {
+                        fail("registers must be listed in increasing offset order without overlap");
+                    }
+                }
+                // `name[n]` declares an array of `n` consecutive registers.
+                let name = word(1);
+                let (name, count) = match name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+                    Some((name, count)) => match parse_number(count) {
+                        Some(count) if count > 0 => (name.to_string(), Some(count)),
+                        _ => fail("register array length must be a positive number"),
+                    },
+                    None => (name, None),
+                };
+                block.registers.push(Register { docs: item_docs, name, offset, count, access, bitfield });
+            }
+            _ => fail("unknown statement"),
+        }
+    }
+
+    RegisterMap { modules }
+}
+
+/// Emit doc comments as `#[doc = "..."]` attributes (valid inside macro invocations too).
+fn write_docs(out: &mut String, indent: &str, docs: &[String]) {
+    for doc in docs {
+        writeln!(out, "{}#[doc = {:?}]", indent, doc).unwrap();
+    }
+}
+
+/// Emit the `<PREFIX>_OFFSET` constant and the `<prefix>()` accessor of a block (or block instance).
+fn write_accessor(out: &mut String, block: &str, prefix: &str, offset: u64, docs: &[String]) {
+    writeln!(out).unwrap();
+    writeln!(out, "    /// Offset of [`{}`] from the peripheral base.", block).unwrap();
+    write_docs(out, "    ", docs);
+    writeln!(out, "    pub const {}_OFFSET: usize = {:#X};", prefix, offset).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    /// Returns the [`{}`] block of the current board.", block).unwrap();
+    write_docs(out, "    ", docs);
+    writeln!(out, "    #[inline]").unwrap();
+    writeln!(out, "    pub fn {}() -> &'static {} {{", prefix.to_lowercase(), block).unwrap();
+    writeln!(out, "        let base = crate::hal::mmio::peripheral_base() + {}_OFFSET;", prefix).unwrap();
+    writeln!(out, "        // Safety: the address is a valid, always-mapped MMIO block and all accesses go").unwrap();
+    writeln!(out, "        // through the volatile cells, so handing out shared references is sound.").unwrap();
+    writeln!(out, "        unsafe {{ &*(base as *const {}) }}", block).unwrap();
+    writeln!(out, "    }}").unwrap();
+}
+
+fn generate_registers(map: &RegisterMap) -> String {
+    let mut out = String::new();
+    writeln!(out, "// @generated by build.rs from {}. Do not edit by hand.", REGISTER_MAP).unwrap();
+
+    for module in &map.modules {
+        writeln!(out).unwrap();
+        for doc in &module.docs {
+            writeln!(out, "#[doc = {:?}]", doc).unwrap();
+        }
+        writeln!(out, "pub mod {} {{", module.name).unwrap();
+        writeln!(out, "    #[allow(unused_imports)]").unwrap();
+        writeln!(out, "    use super::access::{{ReadOnly, ReadWrite, WriteOnly}};").unwrap();
+
+        // Bitfields, through the `register_bitfields!` macro.
+        if !module.bitfields.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "    crate::register_bitfields! {{").unwrap();
+            for bitfield in &module.bitfields {
+                write_docs(&mut out, "        ", &bitfield.docs);
+                writeln!(out, "        {} [", bitfield.name).unwrap();
+                for field in &bitfield.fields {
+                    write_docs(&mut out, "            ", &field.docs);
+                    writeln!(out, "            {} OFFSET({}) NUMBITS({}) [", field.name, field.offset, field.numbits).unwrap();
+                    for value in &field.values {
+                        write_docs(&mut out, "                ", &value.docs);
+                        writeln!(out, "                {} = {},", value.name, value.value).unwrap();
+                    }
+                    writeln!(out, "            ],").unwrap();
+                }
+                writeln!(out, "        ],").unwrap();
+            }
+            writeln!(out, "    }}").unwrap();
+        }
+
+        // Register blocks: base constants, struct with computed padding and offset assertions.
+        for block in &module.blocks {
+            write_accessor(&mut out, &block.name, &block.prefix, block.offset, &[]);
+            for instance in &block.instances {
+                write_accessor(&mut out, &block.name, &instance.prefix, instance.offset, &instance.docs);
+            }
+            writeln!(out).unwrap();
+            write_docs(&mut out, "    ", &block.docs);
+            writeln!(out, "    #[repr(C)]").unwrap();
+            writeln!(out, "    pub struct {} {{", block.name).unwrap();
+            let mut next_offset = 0;
+            let mut reserved = 0;
+            for register in &block.registers {
+                if register.offset > next_offset {
+                    let words = (register.offset - next_offset) / 4;
+                    writeln!(out, "        _reserved{}: [u32; {}], // {:#04X}", reserved, words, next_offset).unwrap();
+                    reserved += 1;
+                }
+                write_docs(&mut out, "        ", &register.docs);
+                let mut cell = match &register.bitfield {
+                    Some(bitfield) => format!("{}<{}::Register>", register.access, bitfield),
+                    None => register.access.to_string(),
+                };
+                if let Some(count) = register.count {
+                    cell = format!("[{}; {}]", cell, count);
+                }
+                writeln!(out, "        pub {}: {}, // {:#04X}", register.name, cell, register.offset).unwrap();
+                next_offset = register.end();
+            }
+            writeln!(out, "    }}").unwrap();
+            writeln!(out).unwrap();
+            for register in &block.registers {
+                writeln!(
+                    out,
+                    "    const _: () = assert!(core::mem::offset_of!({}, {}) == {:#X});",
+                    block.name, register.name, register.offset
+                )
+                .unwrap();
+            }
+            writeln!(
+                out,
+                "    const _: () = assert!(core::mem::size_of::<{}>() == {:#X});",
+                block.name, next_offset
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    }
+    out
 }
\ No newline at end of file
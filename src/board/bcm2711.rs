@@ -0,0 +1,21 @@
+//! Raspberry Pi 4 Model B / 400 / CM4 (BCM2711, Cortex-A72).
+
+use super::{Board, MemoryLayout, Soc};
+
+pub const BOARD: Board = Board {
+    name: "Raspberry Pi 4 (BCM2711)",
+    soc: Soc::Bcm2711,
+    revision: 0,
+    // "Low peripheral" mode, the default set up by the firmware.
+    peripheral_base: 0xFE000000,
+    // The firmware sets UARTCLK to 48 MHz (init_uart_clock).
+    uart_clock_hz: 48_000_000,
+    // The core clock runs at 500 MHz on the Pi 4.
+    core_clock_hz: 500_000_000,
+    act_led_pin: Some(42),
+    memory: MemoryLayout {
+        ram_start: 0,
+        ram_size: 1024 * 1024 * 1024, // 1 GB on the smallest Pi 4
+        kernel_load_address: 0x80000,
+    },
+};
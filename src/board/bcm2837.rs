@@ -0,0 +1,21 @@
+//! Raspberry Pi 3 Model B/B+ / Zero 2 W (BCM2837, Cortex-A53).
+
+use super::{Board, MemoryLayout, Soc};
+
+pub const BOARD: Board = Board {
+    name: "Raspberry Pi 3 / Zero 2 W (BCM2837)",
+    soc: Soc::Bcm2837,
+    revision: 0,
+    peripheral_base: 0x3F000000,
+    // The firmware sets UARTCLK to 48 MHz (init_uart_clock).
+    uart_clock_hz: 48_000_000,
+    // With enable_uart=1 the firmware pins the core clock to 250 MHz.
+    core_clock_hz: 250_000_000,
+    // ACT LED of the Pi 3B+ and Zero 2 W (on the Pi 3B it sits behind the firmware GPIO expander).
+    act_led_pin: Some(29),
+    memory: MemoryLayout {
+        ram_start: 0,
+        ram_size: 512 * 1024 * 1024, // 512 MB on the Zero 2 W, 1 GB on the Pi 3
+        kernel_load_address: 0x80000,
+    },
+};
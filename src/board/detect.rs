@@ -0,0 +1,83 @@
+//! Runtime board detection (`autodetect` feature).
+//!
+//! 1. MIDR_EL1 tells which CPU we run on: Cortex-A53 means BCM2837, Cortex-A72 means BCM2711.
+//!    This is enough to know where the peripherals (and so the mailbox) are.
+//! 2. The firmware board revision gives the exact model (ACT LED pin, RAM size).
+//! 3. The firmware clock rates replace the default UART and core clocks.
+
+use super::{bcm2711, bcm2837, Board, Soc, DEFAULT};
+use crate::drivers::mailbox::{self, clock};
+
+/// MIDR_EL1 part numbers (bits 15:4) of the CPUs used on the supported boards.
+const PART_CORTEX_A53: u32 = 0xD03;
+const PART_CORTEX_A72: u32 = 0xD08;
+
+/// Detect the board the kernel is running on.
+pub fn detect() -> Board {
+    let mut board = match soc_from_midr(read_midr()) {
+        Some(Soc::Bcm2711) => bcm2711::BOARD,
+        Some(Soc::Bcm2837) => bcm2837::BOARD,
+        None => DEFAULT,
+    };
+    // The mailbox is reached through the peripheral window of the board being detected.
+    super::set_peripheral_base(board.peripheral_base);
+
+    if let Some(revision) = mailbox::board_revision() {
+        apply_revision(&mut board, revision);
+    }
+    if let Some(rate) = mailbox::clock_rate(clock::UART) {
+        board.uart_clock_hz = rate;
+    }
+    if let Some(rate) = mailbox::clock_rate(clock::CORE) {
+        board.core_clock_hz = rate;
+    }
+    board
+}
+
+/// Map the CPU part number in MIDR_EL1 to the SoC it is used in.
+fn soc_from_midr(midr: u32) -> Option<Soc> {
+    match (midr >> 4) & 0xFFF {
+        PART_CORTEX_A53 => Some(Soc::Bcm2837),
+        PART_CORTEX_A72 => Some(Soc::Bcm2711),
+        _ => None,
+    }
+}
+
+/// Refine the board description from a (new style) firmware revision code.
+///
+/// Layout: bit 23 new style flag, bits 22:20 memory size, bits 15:12 processor, bits 11:4 type.
+fn apply_revision(board: &mut Board, revision: u32) {
+    board.revision = revision;
+    if revision & (1 << 23) == 0 {
+        return; // Old style codes are only used by the original Pi 1 boards
+    }
+    let (name, act_led_pin) = match (revision >> 4) & 0xFF {
+        0x08 => ("Raspberry Pi 3 Model B", None), // ACT LED behind the firmware GPIO expander
+        0x0A => ("Raspberry Pi Compute Module 3", None),
+        0x0D => ("Raspberry Pi 3 Model B+", Some(29)),
+        0x0E => ("Raspberry Pi 3 Model A+", Some(29)),
+        0x10 => ("Raspberry Pi Compute Module 3+", None),
+        0x11 => ("Raspberry Pi 4 Model B", Some(42)),
+        0x12 => ("Raspberry Pi Zero 2 W", Some(29)),
+        0x13 => ("Raspberry Pi 400", Some(42)),
+        0x14 => ("Raspberry Pi Compute Module 4", Some(42)),
+        _ => return, // Unknown model: keep the SoC defaults
+    };
+    board.name = name;
+    board.act_led_pin = act_led_pin;
+    board.memory.ram_size = (256 * 1024 * 1024) << ((revision >> 20) & 0x7);
+}
+
+/// Read the Main ID Register of the current core.
+#[cfg(target_arch = "aarch64")]
+fn read_midr() -> u32 {
+    let midr: u64;
+    unsafe { core::arch::asm!("mrs {}, midr_el1", out(reg) midr, options(nomem, nostack)) };
+    midr as u32
+}
+
+/// Host builds (e.g. `cargo check` on x86) have no MIDR_EL1: fall back to the default board.
+#[cfg(not(target_arch = "aarch64"))]
+fn read_midr() -> u32 {
+    0
+}
@@ -0,0 +1,110 @@
+//! Board support layer.
+//!
+//! Everything that differs between the supported Raspberry Pi models lives here:
+//! the peripheral base address, the UART reference clocks, the ACT LED pin and the memory layout.
+//! The register blocks in `hal::registers` are located relative to `current().peripheral_base`,
+//! so the drivers themselves are board independent.
+//!
+//! The board is selected at compile time with a cargo feature:
+//! - `bcm2711` (default): Raspberry Pi 4 Model B / 400 / CM4
+//! - `bcm2837`: Raspberry Pi 3 Model B/B+ / Zero 2 W
+//!
+//! With the additional `autodetect` feature a single image runs on all of them: [`init`] reads
+//! MIDR_EL1 to find the SoC, then asks the firmware for the board revision and the real UART and
+//! core clock rates. The compile-time board is only used as a fallback if detection fails.
+//!
+//! ```text
+//! cargo build                                         # Raspberry Pi 4
+//! cargo build --no-default-features --features bcm2837 # Raspberry Pi 3 / Zero 2 W
+//! cargo build --features autodetect                   # Any of them, detected at boot
+//! make run BOARD=rpi3                                 # Pi 3 build, booted under QEMU raspi3b
+//! make run BOARD=auto EMULATE=rpi3                    # Detecting build, booted under QEMU raspi3b
+//! ```
+
+pub mod bcm2711;
+pub mod bcm2837;
+#[cfg(feature = "autodetect")]
+mod detect;
+
+#[cfg(all(feature = "bcm2711", feature = "bcm2837"))]
+compile_error!("Select exactly one board feature: `bcm2711` or `bcm2837` (use --no-default-features).");
+
+#[cfg(not(any(feature = "bcm2711", feature = "bcm2837")))]
+compile_error!("No board selected: enable the `bcm2711` or `bcm2837` feature.");
+
+/// The SoC family of a board. Peripherals that exist or behave differently depend on it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Soc {
+    /// Raspberry Pi 3 / Zero 2 W (Cortex-A53).
+    Bcm2837,
+    /// Raspberry Pi 4 / 400 / CM4 (Cortex-A72).
+    Bcm2711,
+}
+
+/// Memory layout of a board, as seen by the ARM cores.
+pub struct MemoryLayout {
+    /// Start of the ARM RAM.
+    pub ram_start: usize,
+    /// Amount of RAM guaranteed on every variant of the board (the GPU split is not subtracted).
+    pub ram_size: usize,
+    /// Address the firmware loads `kernel8.img` to (must match `linker.ld`).
+    pub kernel_load_address: usize,
+}
+
+/// Description of a supported board.
+pub struct Board {
+    /// Human readable name, printed at boot.
+    pub name: &'static str,
+    /// SoC family.
+    pub soc: Soc,
+    /// Firmware board revision code (0 if unknown, i.e. not detected at runtime).
+    pub revision: u32,
+    /// Base address of the peripheral window (ARM physical address).
+    pub peripheral_base: usize,
+    /// PL011 reference clock (UARTCLK) in Hz, as configured by the firmware.
+    pub uart_clock_hz: u32,
+    /// VPU core clock in Hz. The Mini UART baud rate is derived from it.
+    pub core_clock_hz: u32,
+    /// GPIO pin driving the green ACT LED (`None` if it is not wired to a GPIO, e.g. on the Pi 3B).
+    pub act_led_pin: Option<u8>,
+    /// Memory layout.
+    pub memory: MemoryLayout,
+}
+
+/// The board this kernel was built for (the fallback when `autodetect` is enabled).
+#[cfg(feature = "bcm2711")]
+pub const DEFAULT: Board = bcm2711::BOARD;
+
+/// The board this kernel was built for (the fallback when `autodetect` is enabled).
+#[cfg(all(feature = "bcm2837", not(feature = "bcm2711")))]
+pub const DEFAULT: Board = bcm2837::BOARD;
+
+// The board in use. Only written by `init`, which runs once on the boot core before any driver.
+static mut CURRENT: Board = DEFAULT;
+
+/// Initialize the board layer. Must be the first thing `main` does, before any driver is used.
+///
+/// With the `autodetect` feature this detects the board the kernel is running on,
+/// otherwise the compile-time board is kept.
+pub fn init() {
+    #[cfg(feature = "autodetect")]
+    {
+        let board = detect::detect();
+        // Safety: single core, called once before anything else reads `CURRENT`.
+        unsafe { *core::ptr::addr_of_mut!(CURRENT) = board };
+    }
+}
+
+/// Returns the board the kernel is running on.
+#[inline]
+pub fn current() -> &'static Board {
+    // Safety: `CURRENT` is only written by `init`, before any reference is handed out.
+    unsafe { &*core::ptr::addr_of!(CURRENT) }
+}
+
+/// Temporarily switch the peripheral base (used by detection before the mailbox can be queried).
+#[cfg(feature = "autodetect")]
+fn set_peripheral_base(base: usize) {
+    // Safety: see `init`.
+    unsafe { (*core::ptr::addr_of_mut!(CURRENT)).peripheral_base = base };
+}
@@ -0,0 +1,78 @@
+//! Console device
+//!
+//! `print!`/`println!` (and the output history of `log`) go to the serial port selected here,
+//! UART0 until another one is selected. Select the console right after initializing its UART,
+//! e.g. the Mini UART when UART0 drives the Bluetooth module:
+//!
+//! ```rust
+//! use crate::drivers::uart::{mini_uart, MiniUartSerial};
+//!
+//! mini_uart::init(peripherals.mini_uart, &UartConfig::default())?;
+//! console::set(&MiniUartSerial);
+//! println!("Console on {}", console::get().name()); // "Console on mini_uart"
+//! ```
+
+use crate::drivers::uart::{SerialPort, Uart0Serial};
+use crate::interrupts;
+
+/// Send console output to `device` from now on. Pending output of the previous console is sent first.
+pub fn set(device: &'static dyn SerialPort) {
+    let previous = get();
+    previous.flush();
+    interrupts::without_interrupts(|| with_console(|console| *console = device));
+}
+
+/// The current console device.
+pub fn get() -> &'static dyn SerialPort {
+    interrupts::without_interrupts(|| with_console(|console| *console))
+}
+
+/// Run `f` with the console device.
+#[cfg(not(test))]
+fn with_console<T>(f: impl FnOnce(&mut &'static dyn SerialPort) -> T) -> T {
+    static mut CONSOLE: &dyn SerialPort = &Uart0Serial;
+    // Safety: single core, and the console is only used with IRQs masked.
+    f(unsafe { &mut *core::ptr::addr_of_mut!(CONSOLE) })
+}
+
+/// Run `f` with the console device. Host tests get one console per test thread.
+#[cfg(test)]
+fn with_console<T>(f: impl FnOnce(&mut &'static dyn SerialPort) -> T) -> T {
+    extern crate std;
+    std::thread_local! {
+        static CONSOLE: core::cell::RefCell<&'static dyn SerialPort> =
+            const { core::cell::RefCell::new(&Uart0Serial) };
+    }
+    CONSOLE.with(|console| f(&mut console.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::peripherals::Peripherals;
+    use crate::drivers::uart::{mini_uart, MiniUartSerial, UartConfig};
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::mailbox::MAILBOX_REGS_OFFSET;
+    use crate::hal::registers::uart::{MINI_UART_REGS_OFFSET, PL011_UART_REGS_OFFSET};
+
+    const AUX_MU_IO: usize = MINI_UART_REGS_OFFSET;
+    const AUX_MU_LSR: usize = MINI_UART_REGS_OFFSET + 0x14;
+    const DR: usize = PL011_UART_REGS_OFFSET;
+
+    #[test]
+    fn println_goes_to_the_selected_console() {
+        sim::reset();
+        sim::poke(PL011_UART_REGS_OFFSET + 0x18, 1 << 7); // UART0 FR: TXFE
+        crate::print!("A");
+        assert_eq!(sim::writes(DR), [b'A' as u32]);
+        assert_eq!(get().name(), "uart0");
+
+        sim::poke(MAILBOX_REGS_OFFSET + 0x18, 1 << 30); // No firmware to ask for the core clock
+        mini_uart::init(unsafe { Peripherals::steal() }.mini_uart, &UartConfig::default()).unwrap();
+        sim::poke(AUX_MU_LSR, (1 << 5) | (1 << 6)); // TX empty, idle
+        set(&MiniUartSerial);
+        crate::print!("B");
+        assert_eq!(sim::writes(AUX_MU_IO).last(), Some(&(b'B' as u32)));
+        assert_eq!(sim::writes(DR).len(), 1); // Nothing more on UART0
+    }
+}
@@ -0,0 +1,222 @@
+//! Emergency debug menu
+//!
+//! Sending a break on the console (e.g. Ctrl-A F in minicom, Ctrl-A \ in picocom) drops into a
+//! small menu, like the Linux magic SysRq key: it works even when the main program is stuck in a
+//! loop, as long as IRQs are unmasked.
+//!
+//! It needs the console on UART0 (see `console`): only the PL011 detects breaks, and the menu reads
+//! its keys from UART0. `install` refuses to install it on another console, and a break is ignored
+//! if the console was moved away from UART0 since.
+//!
+//! The menu runs from the UART interrupt, with IRQs masked. UART0 is switched to polled mode while
+//! it is open (the ring buffers only move in interrupt mode) and back when it is left; bytes
+//! received before the break are dropped.
+//!
+//! # Example
+//! ```rust
+//! interrupts::init();
+//! uart0::enable_interrupts();
+//! assert!(debug_menu::install()); // Break on the console: menu
+//! interrupts::enable();
+//! ```
+
+use crate::board;
+use crate::console;
+use crate::drivers::pins;
+use crate::drivers::uart::uart0;
+use crate::drivers::watchdog;
+use crate::hal::registers::uart::pl011_uart_regs;
+use crate::interrupts;
+use crate::{print, println};
+
+/// Enter the menu whenever a break is received on UART0 (interrupt mode only). Returns `false`,
+/// installing nothing, if the console is not UART0.
+pub fn install() -> bool {
+    if !console_is_uart0() {
+        return false;
+    }
+    uart0::set_break_handler(Some(on_break));
+    true
+}
+
+/// Break handler of UART0.
+fn on_break() {
+    if !console_is_uart0() {
+        return; // The menu would print elsewhere, and use another driver from the interrupt
+    }
+    uart0::disable_interrupts();
+    run();
+    uart0::enable_interrupts();
+}
+
+/// Show the menu and run commands until the user leaves it.
+pub fn run() {
+    println!();
+    println!("*** Debug menu ({}) ***", board::current().name);
+    print_help();
+    loop {
+        print!("debug> ");
+        let key = loop {
+            if let Some(byte) = uart0::read_byte() {
+                break byte;
+            }
+        };
+        match key {
+            b'r' => {
+                println!("r");
+                dump_registers();
+            }
+            b'l' => {
+                println!("l");
+                println!("--- output history ---");
+                crate::log::dump_history();
+                println!("--- end of history ---");
+            }
+            b'p' => {
+                println!("p");
+                pins::report();
+            }
+            b'b' => {
+                println!("b");
+                println!("Rebooting...");
+                uart0::flush();
+                watchdog::reboot();
+            }
+            b'c' | b'q' | b'\r' => {
+                println!("c");
+                return;
+            }
+            0 => println!(), // A NUL byte (further breaks are dropped by `read_byte`)
+            _ => {
+                println!();
+                print_help();
+            }
+        }
+    }
+}
+
+fn console_is_uart0() -> bool {
+    console::get().name() == "uart0"
+}
+
+fn print_help() {
+    println!("  r  registers of the interrupted code");
+    println!("  l  log dump (recent console output)");
+    println!("  p  GPIO pin owners");
+    println!("  b  reboot");
+    println!("  c  continue");
+}
+
+/// Print the registers of the code the break interrupted (saved by `irq_entry`) and the state of
+/// UART0.
+fn dump_registers() {
+    match interrupts::interrupted_context() {
+        Some(context) => {
+            let pstate = context.pstate;
+            println!(
+                "Interrupted at PC 0x{:016X}  SP 0x{:016X}  EL{}  DAIF 0x{:X}  NZCV 0x{:X}",
+                context.pc,
+                context.sp,
+                (pstate >> 2) & 0b11,
+                (pstate >> 6) & 0xF,
+                (pstate >> 28) & 0xF
+            );
+            for (row, registers) in context.frame.x.chunks(4).enumerate() {
+                for (column, value) in registers.iter().enumerate() {
+                    print!("x{:<2} 0x{:016X}  ", row * 4 + column, value);
+                }
+                println!();
+            }
+            println!("fp  0x{:016X}  lr  0x{:016X}", context.frame.fp, context.frame.lr);
+        }
+        None => println!("Not entered from an interrupt: no interrupted registers"),
+    }
+    let uart = pl011_uart_regs();
+    println!(
+        "UART0  CR 0x{:04X}  FR 0x{:03X}  LCRH 0x{:02X}  IBRD {}  FBRD {}  IMSC 0x{:03X}  RIS 0x{:03X}",
+        uart.cr.get(),
+        uart.fr.get(),
+        uart.lcrh.get(),
+        uart.ibrd.get(),
+        uart.fbrd.get(),
+        uart.imsc.get(),
+        uart.ris.get()
+    );
+    println!("UART0 receive errors: {}", uart0::error_counts());
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::drivers::uart::pl011::Pl011;
+    use crate::drivers::uart::MiniUartSerial;
+    use crate::hal::mmio::sim::{self, Access};
+    use crate::hal::registers::uart::{MINI_UART_REGS_OFFSET, PL011_UART_REGS_OFFSET};
+    use std::vec::Vec;
+
+    const DR: usize = PL011_UART_REGS_OFFSET;
+    const FR: usize = PL011_UART_REGS_OFFSET + 0x18;
+    const IMSC: usize = PL011_UART_REGS_OFFSET + 0x38;
+    const MIS: usize = PL011_UART_REGS_OFFSET + 0x40;
+    const AUX_MU_IO: usize = MINI_UART_REGS_OFFSET;
+    const AUX_MU_LSR: usize = MINI_UART_REGS_OFFSET + 0x14;
+
+    /// Receive a break on UART0 and run its interrupt handler; `keys` are the next bytes received.
+    fn receive_break(keys: &[u32]) {
+        sim::queue_reads(DR, &[(1 << 10) | (1 << 8)]); // The break: a 0 byte with BE (and FE)
+        sim::queue_reads(DR, keys);
+        sim::queue_reads(FR, &[1 << 7, (1 << 7) | (1 << 4)]); // Only the break word is in the FIFO
+        sim::poke(MIS, 1 << 6); // Receive timeout
+        Pl011::handle_interrupt();
+    }
+
+    #[test]
+    fn the_menu_is_only_installed_on_a_uart0_console() {
+        sim::reset();
+        assert!(install());
+        sim::poke(PL011_UART_REGS_OFFSET + 0x18, 1 << 7); // UART0 FR: TXFE, for the console switch
+        console::set(&MiniUartSerial);
+        assert!(!install());
+    }
+
+    #[test]
+    fn a_break_opens_the_menu_in_polled_mode_until_c() {
+        sim::reset();
+        uart0::enable_interrupts();
+        assert!(install());
+        sim::poke(FR, 1 << 7); // TX FIFO empty, RX FIFO not empty (for the keys)
+        receive_break(&[b'c' as u32]);
+        let imsc = sim::writes(IMSC);
+        assert_eq!(imsc[imsc.len() - 2..], [0, 0x50]); // Polled while open, then RX and RT again
+        let output: Vec<u8> = sim::writes(DR).into_iter().map(|word| word as u8).collect();
+        assert!(output.windows(10).any(|text| text == b"Debug menu"));
+        assert!(output.ends_with(b"debug> c\r\n"));
+        assert!(uart0::interrupts_enabled());
+        sim::poke(FR, (1 << 7) | (1 << 4)); // TX FIFO and RX FIFO empty
+        uart0::disable_interrupts();
+    }
+
+    #[test]
+    fn a_break_is_ignored_once_the_console_left_uart0() {
+        sim::reset();
+        uart0::enable_interrupts();
+        assert!(install());
+        sim::poke(FR, 1 << 7); // TXFE, for the console switch
+        console::set(&MiniUartSerial);
+        sim::poke(AUX_MU_LSR, 1 << 5); // Transmitter empty: a wrongly opened menu would not block
+        let before = sim::trace().len();
+        receive_break(&[b'c' as u32]);
+        let trace = sim::trace();
+        let written = |offset| {
+            trace[before..].iter().any(|access| matches!(access, Access::Write { offset: o, .. } if *o == offset))
+        };
+        assert!(!written(IMSC)); // Still in interrupt mode: the menu did not run
+        assert!(!written(AUX_MU_IO));
+        assert!(!written(DR));
+        assert!(uart0::interrupts_enabled());
+        sim::poke(FR, (1 << 7) | (1 << 4)); // TX FIFO and RX FIFO empty
+        uart0::disable_interrupts();
+    }
+}
@@ -0,0 +1,207 @@
+//! embedded-hal 1.0 / embedded-io trait implementations (feature `embedded-hal`)
+//!
+//! Lets ecosystem device drivers (displays, sensors, ...) run on top of our drivers:
+//! - [`GpioPin`] and the type-state [`Pin`]: `OutputPin`, `StatefulOutputPin`, `InputPin`
+//! - [`Uart0Serial`] / [`MiniUartSerial`]: `embedded_io::Read`, `ReadReady` and `Write`
+//! - [`Delay`]: `DelayNs`, timed with the ARM generic timer
+//!
+//! None of our operations can fail once a pin is claimed or a UART is initialized, so every error
+//! type is `Infallible`. `Read` drops bytes received with an error, like `read_byte` (they are
+//! counted in `error_counts`). There are no SPI or I2C drivers yet, so no `SpiBus` / `I2c` either.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::ehal::{Delay, Uart0Serial};
+//! use embedded_hal::delay::DelayNs;
+//! use embedded_hal::digital::OutputPin;
+//!
+//! uart0::init(peripherals.uart0, &UartConfig::default())?;
+//! let mut serial = Uart0Serial; // embedded_io::Write, e.g. for a modem or GPS driver
+//! let mut reset = GpioPin::new(25, "display")?;
+//! reset.set_output();
+//! reset.set_low()?;
+//! Delay.delay_ms(10);
+//! reset.set_high()?;
+//! ```
+
+use core::convert::Infallible;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, InputPin, OutputPin, StatefulOutputPin};
+
+use super::gpio::{GpioPin, Input, Output, Pin};
+use super::timer::counter;
+use super::uart::{mini_uart, uart0};
+
+pub use super::uart::{MiniUartSerial, Uart0Serial};
+
+impl digital::ErrorType for GpioPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for GpioPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        GpioPin::set_low(self);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        GpioPin::set_high(self);
+        Ok(())
+    }
+}
+
+impl StatefulOutputPin for GpioPin {
+    fn is_set_high(&mut self) -> Result<bool, Infallible> {
+        Ok(GpioPin::is_set_high(self))
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Infallible> {
+        Ok(GpioPin::is_set_low(self))
+    }
+}
+
+impl InputPin for GpioPin {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(GpioPin::is_high(self))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(GpioPin::is_low(self))
+    }
+}
+
+impl<const N: u8, MODE> digital::ErrorType for Pin<N, MODE> {
+    type Error = Infallible;
+}
+
+impl<const N: u8> OutputPin for Pin<N, Output> {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Pin::set_low(self);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Pin::set_high(self);
+        Ok(())
+    }
+}
+
+impl<const N: u8> StatefulOutputPin for Pin<N, Output> {
+    fn is_set_high(&mut self) -> Result<bool, Infallible> {
+        Ok(Pin::is_set_high(self))
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Infallible> {
+        Ok(Pin::is_set_low(self))
+    }
+}
+
+impl<const N: u8> InputPin for Pin<N, Input> {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(Pin::is_high(self))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(Pin::is_low(self))
+    }
+}
+
+/// Implement the `embedded_io` traits for a serial port type on top of a UART driver module.
+macro_rules! impl_serial {
+    ($serial:ty, $uart:ident) => {
+        impl embedded_io::ErrorType for $serial {
+            type Error = Infallible;
+        }
+
+        impl embedded_io::Read for $serial {
+            /// Block until at least one byte arrives, then read what is available (up to `buf.len()`).
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+                let mut count = 0;
+                while count < buf.len() {
+                    match $uart::read_byte() {
+                        Some(byte) => {
+                            buf[count] = byte;
+                            count += 1;
+                        }
+                        None if count > 0 => break,
+                        None => core::hint::spin_loop(),
+                    }
+                }
+                Ok(count)
+            }
+        }
+
+        impl embedded_io::ReadReady for $serial {
+            fn read_ready(&mut self) -> Result<bool, Infallible> {
+                Ok($uart::is_data_ready())
+            }
+        }
+
+        impl embedded_io::Write for $serial {
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+                for &byte in buf {
+                    $uart::write_byte(byte);
+                }
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> Result<(), Infallible> {
+                $uart::flush();
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_serial!(Uart0Serial, uart0);
+impl_serial!(MiniUartSerial, mini_uart);
+
+/// Busy-wait delay on the ARM generic timer (CNTPCT_EL0 at CNTFRQ_EL0, 54 MHz on the Pi 4).
+#[derive(Clone, Copy, Default)]
+pub struct Delay;
+
+impl DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        let (frequency, start) = counter();
+        // Round up: never wait shorter than asked
+        let ticks = (ns as u64 * frequency).div_ceil(1_000_000_000);
+        while counter().1.wrapping_sub(start) < ticks {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::gpio::GPIO_REGS_OFFSET;
+    use crate::hal::registers::uart::PL011_UART_REGS_OFFSET;
+    use embedded_io::{Read, Write};
+
+    const GPSET0: usize = GPIO_REGS_OFFSET + 0x1C;
+    const GPLEV0: usize = GPIO_REGS_OFFSET + 0x34;
+    const DR: usize = PL011_UART_REGS_OFFSET;
+
+    /// Written against the traits only, like an ecosystem driver.
+    fn pulse(pin: &mut impl OutputPin, delay: &mut impl DelayNs) {
+        pin.set_high().unwrap();
+        delay.delay_us(10);
+    }
+
+    #[test]
+    fn traits_forward_to_the_drivers() {
+        sim::reset();
+        let mut pin = Pin::<4, Input>::take("test").unwrap().into_output();
+        pulse(&mut pin, &mut Delay);
+        assert_eq!(sim::writes(GPSET0), [1 << 4]);
+        sim::poke(GPLEV0, 1 << 4);
+        assert_eq!(StatefulOutputPin::is_set_high(&mut pin), Ok(true));
+
+        Uart0Serial.write_all(b"ok").unwrap();
+        assert_eq!(sim::writes(DR), [b'o' as u32, b'k' as u32]);
+        let mut buf = [0; 4];
+        assert_eq!(Uart0Serial.read(&mut buf), Ok(4)); // RX FIFO never empty in the simulation
+    }
+}
@@ -1,80 +1,570 @@
 //! GPIO driver for Raspberry Pi 4
 //!
-//! Provides basic GPIO pin control: set as output, set high, set low.
+//! Provides basic GPIO pin control: set as input or output, set high/low, read the pin level,
+//! and edge/level event detection with per-pin handlers run from the GPIO interrupt.
 //! This driver is generic and can be used for any GPIO pin (0-53).
+//! Every `GpioPin` claims its pin in the pin registry (`drivers::pins`) while it exists,
+//! so two handles (or a handle and a driver such as `uart0`) can never fight over a pin.
 //!
 //! # Example
 //! 
 //! ```rust
 //! use drivers::gpio::GpioPin;
 //!
-//! // Create a GPIO pin instance for GPIO 42 (ACT LED)
-//! let led = GpioPin::new(42);
+//! // Create a GPIO pin instance for GPIO 42 (ACT LED), owned by "act_led"
+//! let led = GpioPin::new(42, "act_led")?;
 //! led.set_output();
 //! led.set_high(); // Turn LED on
 //! led.set_low();  // Turn LED off
+//!
+//! // Read a button on GPIO 17
+//! let button = GpioPin::new(17, "button")?;
+//! button.set_input();
+//! if button.is_low() { /* pressed (active low) */ }
+//!
+//! // Route a peripheral signal to a pin by name (see `drivers::pinmux`)
+//! let sda = GpioPin::new(2, "i2c1")?;
+//! sda.set_signal("SDA1")?;              // ALT0
+//! sda.set_pull(Pull::Up);
+//! // Drivers claim and configure all their pins at once
+//! gpio::request_signals(&[(14, "TXD0"), (15, "RXD0")], "uart0")?;
+//!
+//! // React to a button press from the GPIO interrupt instead of polling
+//! fn pressed(pin: u8) { println!("Button on GPIO{} pressed", pin); }
+//! button.enable_event(Event::FallingEdge);
+//! button.on_event(pressed);
 //! ```
+//!
+//! When the pin number is known at compile time, prefer the type-state [`Pin`] (see [`typed`]):
+//! invalid pins and e.g. `set_high` on an input are compile errors there. To drive several pins
+//! of a parallel bus at once, use a [`GpioPort`]. Drive strength, slew rate and hysteresis are
+//! set per bank of pads in [`pads`].
 
-use crate::hal::registers::gpio::{GPIO_REGS, GpioRegisters};
+pub mod pads;
+pub mod port;
+pub mod typed;
+
+pub use port::{Bank, GpioPort};
+pub use typed::{Alt, Input, Output, Pin};
+
+use super::pinmux;
+use super::pins::{self, PinError};
+use crate::board::{self, Soc};
+use crate::hal::registers::access::{Field, ReadWrite};
+use crate::hal::registers::gpio::{gpio_regs, GPFSEL, GPIO_PUP_PDN_CNTRL, GPPUD};
+use crate::interrupts::{self, irq};
+
+/// Function of a GPIO pin, with the value of its 3 function select bits in GPFSELn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Function {
+    Input = 0b000,
+    Output = 0b001,
+    Alt0 = 0b100,
+    Alt1 = 0b101,
+    Alt2 = 0b110,
+    Alt3 = 0b111,
+    Alt4 = 0b011,
+    Alt5 = 0b010,
+}
 
-/// Represents a GPIO pin number (0-53).
-pub struct GpioPin(u8);
+impl Function {
+    /// Short name, as used in datasheets and the pin report.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Function::Input => "INPUT",
+            Function::Output => "OUTPUT",
+            Function::Alt0 => "ALT0",
+            Function::Alt1 => "ALT1",
+            Function::Alt2 => "ALT2",
+            Function::Alt3 => "ALT3",
+            Function::Alt4 => "ALT4",
+            Function::Alt5 => "ALT5",
+        }
+    }
+}
+
+/// Pull resistor of a GPIO pin.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pull {
+    /// No pull resistor (floating input).
+    None,
+    /// Pull-up resistor (idle high).
+    Up,
+    /// Pull-down resistor (idle low).
+    Down,
+}
+
+/// Event that sets the bit of a pin in the event detect status register (GPEDSn).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// Rising edge, sampled with the system clock (filters glitches).
+    RisingEdge,
+    /// Falling edge, sampled with the system clock (filters glitches).
+    FallingEdge,
+    /// High level: the status bit stays set while the pin is high.
+    High,
+    /// Low level: the status bit stays set while the pin is low.
+    Low,
+    /// Rising edge, not sampled (detects very short pulses).
+    AsyncRisingEdge,
+    /// Falling edge, not sampled (detects very short pulses).
+    AsyncFallingEdge,
+}
+
+impl Event {
+    const ALL: [Event; 6] = [
+        Event::RisingEdge,
+        Event::FallingEdge,
+        Event::High,
+        Event::Low,
+        Event::AsyncRisingEdge,
+        Event::AsyncFallingEdge,
+    ];
+}
+
+/// Handler for the events of one pin. Runs from the GPIO interrupt and gets the pin number.
+pub type EventHandler = fn(u8);
+
+/// Represents a claimed GPIO pin (0-53). The claim is released when the pin is dropped.
+pub struct GpioPin {
+    pin: u8,
+    owner: &'static str,
+}
 
 impl GpioPin {
-    /// Create a new GPIO pin instance.
+    /// Claim a GPIO pin for `owner`.
     /// # Arguments
     /// * `pin` - GPIO pin number (0-53)
-    pub const fn new(pin: u8) -> Self {
-        GpioPin(pin)
+    /// * `owner` - Name shown in the pin report (e.g. "act_led")
+    ///
+    /// Fails if the pin does not exist or is already owned, even by `owner` (another handle).
+    pub fn new(pin: u8, owner: &'static str) -> Result<Self, PinError> {
+        pins::claim_new(pin, owner, Function::Input)?;
+        Ok(GpioPin { pin, owner })
+    }
+
+    /// Set this GPIO pin as input.
+    /// This configures the pin's function select bits to '000' (input).
+    pub fn set_input(&self) {
+        self.set_function(Function::Input);
     }
 
     /// Set this GPIO pin as output.
     /// This configures the pin's function select bits to '001' (output).
     pub fn set_output(&self) {
-        let pin = self.0;
-        let fsel_index = pin / 10; // Each GPFSEL controls 10 pins
-        let fsel_shift = (pin % 10) * 3;
-        unsafe {
-            let regs = &mut *GPIO_REGS;
-            let fsel = match fsel_index {
-                0 => &mut regs.gpfsel0,
-                1 => &mut regs.gpfsel1,
-                2 => &mut regs.gpfsel2,
-                3 => &mut regs.gpfsel3,
-                4 => &mut regs.gpfsel4,
-                5 => &mut regs.gpfsel5,
-                _ => return, // Invalid pin
-            };
-            let mut val = core::ptr::read_volatile(fsel);
-            val &= !(0b111 << fsel_shift); // Clear function bits
-            val |= 0b001 << fsel_shift;   // Set to output (001)
-            core::ptr::write_volatile(fsel, val);
-        }
+        self.set_function(Function::Output);
+    }
+
+    /// Select any function (input, output or ALT0-ALT5) for this GPIO pin.
+    pub fn set_function(&self, function: Function) {
+        // Cannot fail: we own the pin, and only record its new function
+        let _ = pins::claim(self.pin, self.owner, function);
+        write_function(self.pin, function);
+    }
+
+    /// Select the alternate function in which this pin carries `signal` (e.g. "SDA1").
+    /// Fails if the pin cannot carry it, leaving the pin unchanged.
+    pub fn set_signal(&self, signal: &'static str) -> Result<(), PinError> {
+        self.set_function(pinmux::find(self.pin, signal)?);
+        Ok(())
+    }
+
+    /// Enable the pull-up or pull-down resistor of this pin, or disable both.
+    pub fn set_pull(&self, pull: Pull) {
+        set_pull(self.pin, pull);
     }
 
     /// Set this GPIO pin high (logic 1).
     pub fn set_high(&self) {
-        let pin = self.0;
-        unsafe {
-            let regs = &mut *GPIO_REGS;
-            if pin < 32 {
-                regs.gpset0 = 1 << pin;
-            } else {
-                regs.gpset1 = 1 << (pin - 32);
-            }
+        let (bank, bit) = self.bank_bit();
+        let regs = gpio_regs();
+        if bank == 0 {
+            regs.gpset0.set(bit);
+        } else {
+            regs.gpset1.set(bit);
         }
     }
 
     /// Set this GPIO pin low (logic 0).
     pub fn set_low(&self) {
-        let pin = self.0;
-        unsafe {
-            let regs = &mut *GPIO_REGS;
-            if pin < 32 {
-                regs.gpclr0 = 1 << pin;
-            } else {
-                regs.gpclr1 = 1 << (pin - 32);
+        let (bank, bit) = self.bank_bit();
+        let regs = gpio_regs();
+        if bank == 0 {
+            regs.gpclr0.set(bit);
+        } else {
+            regs.gpclr1.set(bit);
+        }
+    }
+
+    /// Read the level of this GPIO pin (GPLEVn). Works in any function, e.g. for a button input.
+    pub fn is_high(&self) -> bool {
+        let (bank, bit) = self.bank_bit();
+        let regs = gpio_regs();
+        let level = if bank == 0 { regs.gplev0.get() } else { regs.gplev1.get() };
+        level & bit != 0
+    }
+
+    /// Read the level of this GPIO pin: `true` if it is low.
+    pub fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+
+    /// Check whether this output pin is driven high.
+    /// The output latch cannot be read back on the BCM SoCs, but the level register reflects the
+    /// driven level of an output (unless the pin is shorted or overloaded externally).
+    pub fn is_set_high(&self) -> bool {
+        self.is_high()
+    }
+
+    /// Check whether this output pin is driven low.
+    pub fn is_set_low(&self) -> bool {
+        !self.is_set_high()
+    }
+
+    /// Start detecting `event` on this pin. Several events can be enabled at the same time.
+    pub fn enable_event(&self, event: Event) {
+        let (bank, bit) = self.bank_bit();
+        let enable = detect_enable(event, bank);
+        enable.set(enable.get() | bit);
+    }
+
+    /// Stop detecting `event` on this pin.
+    pub fn disable_event(&self, event: Event) {
+        let (bank, bit) = self.bank_bit();
+        let enable = detect_enable(event, bank);
+        enable.set(enable.get() & !bit);
+    }
+
+    /// Stop detecting all events on this pin.
+    pub fn disable_events(&self) {
+        for event in Event::ALL {
+            self.disable_event(event);
+        }
+    }
+
+    /// Check whether an enabled event was detected on this pin since the last [`clear_event`](Self::clear_event).
+    pub fn event_detected(&self) -> bool {
+        let (bank, bit) = self.bank_bit();
+        let regs = gpio_regs();
+        let status = if bank == 0 { regs.gpeds0.get() } else { regs.gpeds1.get() };
+        status & bit != 0
+    }
+
+    /// Clear the detected event of this pin (GPEDSn bits are cleared by writing 1).
+    pub fn clear_event(&self) {
+        let (bank, bit) = self.bank_bit();
+        let regs = gpio_regs();
+        if bank == 0 {
+            regs.gpeds0.set(bit);
+        } else {
+            regs.gpeds1.set(bit);
+        }
+    }
+
+    /// Run `handler` from the GPIO interrupt whenever an enabled event is detected on this pin.
+    ///
+    /// The event is cleared before the handler runs. With a level event (`High`/`Low`) the handler
+    /// is called again as long as the level persists, so it should disable the event.
+    /// Note: once a handler is registered, the GPIO interrupt also clears events of pins without
+    /// a handler (it cannot leave them pending), so do not poll `event_detected` at the same time.
+    pub fn on_event(&self, handler: EventHandler) {
+        interrupts::without_interrupts(|| {
+            with_event_handlers(|handlers| handlers[self.pin as usize] = Some(handler))
+        });
+        for bank_irq in [irq::GPIO_BANK0, irq::GPIO_BANK1, irq::GPIO_BANK2] {
+            if !interrupts::is_registered(bank_irq) {
+                interrupts::register(bank_irq, handle_interrupt);
             }
         }
     }
+
+    /// Remove the event handler of this pin.
+    pub fn remove_event_handler(&self) {
+        interrupts::without_interrupts(|| with_event_handlers(|handlers| handlers[self.pin as usize] = None));
+    }
+
+    /// Register bank (0: pins 0-31, 1: pins 32-53) and bit of this pin in the per-pin registers.
+    fn bank_bit(&self) -> (usize, u32) {
+        ((self.pin / 32) as usize, 1 << (self.pin % 32))
+    }
+}
+
+/// Claim pins for `owner` and route the given signals to them, e.g. `[(14, "TXD0"), (15, "RXD0")]`.
+///
+/// Used by drivers that own their pins for good (no `GpioPin` handle). Nothing is claimed or
+/// configured if one of the pins is taken, cannot carry its signal or is listed twice.
+pub fn request_signals(signals: &[(u8, &'static str)], owner: &'static str) -> Result<(), PinError> {
+    let mut functions = [(0, Function::Input); pins::PIN_COUNT];
+    for (index, &(pin, signal)) in signals.iter().enumerate() {
+        let function = pinmux::find(pin, signal)?;
+        if signals[..index].iter().any(|&(other, _)| other == pin) {
+            return Err(PinError::DuplicatePin(pin));
+        }
+        functions[index] = (pin, function); // Only distinct, existing pins so far: index < PIN_COUNT
+    }
+    let functions = &functions[..signals.len()];
+    pins::claim_all(functions, owner)?;
+    for &(pin, function) in functions.iter() {
+        write_function(pin, function);
+    }
+    Ok(())
+}
+
+/// Write the function select bits of `pin`, keeping the other pins of its GPFSEL register.
+fn write_function(pin: u8, function: Function) {
+    let fsel_index = pin / 10; // Each GPFSEL controls 10 pins
+    let fsel_shift = (pin % 10) * 3;
+    let regs = gpio_regs();
+    let fsel = match fsel_index {
+        0 => &regs.gpfsel0,
+        1 => &regs.gpfsel1,
+        2 => &regs.gpfsel2,
+        3 => &regs.gpfsel3,
+        4 => &regs.gpfsel4,
+        5 => &regs.gpfsel5,
+        _ => return, // Invalid pin
+    };
+    let field = Field::<GPFSEL::Register>::new(0b111, fsel_shift as u32);
+    fsel.modify(field.val(function as u32)); // Set the function, keep the other pins
+}
+
+/// Configure the pull resistor of `pin`. For drivers that claimed the pin with [`request_signals`].
+///
+/// The BCM2711 has a pull control register with 2 bits per pin. Older SoCs (BCM2835/BCM2837)
+/// need a timed sequence: set the pull in GPPUD, wait, clock it into the pin through GPPUDCLKn,
+/// wait, then remove both again.
+pub fn set_pull(pin: u8, pull: Pull) {
+    if pin as usize >= pins::PIN_COUNT {
+        return; // Invalid pin
+    }
+    let regs = gpio_regs();
+    match board::current().soc {
+        Soc::Bcm2711 => {
+            let control = match pin / 16 { // Each register controls 16 pins
+                0 => &regs.gpio_pup_pdn_cntrl_reg0,
+                1 => &regs.gpio_pup_pdn_cntrl_reg1,
+                2 => &regs.gpio_pup_pdn_cntrl_reg2,
+                _ => &regs.gpio_pup_pdn_cntrl_reg3,
+            };
+            let value = match pull {
+                Pull::None => GPIO_PUP_PDN_CNTRL::PULL0::None,
+                Pull::Up => GPIO_PUP_PDN_CNTRL::PULL0::Up,
+                Pull::Down => GPIO_PUP_PDN_CNTRL::PULL0::Down,
+            };
+            let field = Field::<GPIO_PUP_PDN_CNTRL::Register>::new(0b11, (pin % 16) as u32 * 2);
+            control.modify(field.val(value.value)); // Set the pull, keep the other pins
+        }
+        Soc::Bcm2837 => {
+            let (bank, bit) = ((pin / 32) as usize, 1 << (pin % 32));
+            let clock = if bank == 0 { &regs.gppudclk0 } else { &regs.gppudclk1 };
+            regs.gppud.write(match pull {
+                Pull::None => GPPUD::PUD::Off,
+                Pull::Up => GPPUD::PUD::Up,
+                Pull::Down => GPPUD::PUD::Down,
+            });
+            delay_cycles(150); // Set-up time for the control signal
+            clock.set(bit); // Clock the control signal into the pin
+            delay_cycles(150); // Hold time for the control signal
+            regs.gppud.write(GPPUD::PUD::Off); // Remove the control signal
+            clock.set(0); // Remove the clock
+        }
+    }
+}
+
+/// Busy-wait for at least `cycles` CPU cycles.
+fn delay_cycles(cycles: u32) {
+    for _ in 0..cycles {
+        core::hint::spin_loop();
+    }
+}
+
+/// Detect enable register of `event` for register bank `bank` (0 or 1).
+fn detect_enable(event: Event, bank: usize) -> &'static ReadWrite {
+    let regs = gpio_regs();
+    let (bank0, bank1) = match event {
+        Event::RisingEdge => (&regs.gpren0, &regs.gpren1),
+        Event::FallingEdge => (&regs.gpfen0, &regs.gpfen1),
+        Event::High => (&regs.gphen0, &regs.gphen1),
+        Event::Low => (&regs.gplen0, &regs.gplen1),
+        Event::AsyncRisingEdge => (&regs.gparen0, &regs.gparen1),
+        Event::AsyncFallingEdge => (&regs.gpafen0, &regs.gpafen1),
+    };
+    if bank == 0 { bank0 } else { bank1 }
+}
+
+/// GPIO bank interrupt handler: clear the detected events and run the handlers of their pins.
+pub fn handle_interrupt() {
+    let regs = gpio_regs();
+    for (bank, status) in [&regs.gpeds0, &regs.gpeds1].into_iter().enumerate() {
+        let mut detected = status.get();
+        status.set(detected); // Clear first, so an event arriving during a handler is not lost
+        while detected != 0 {
+            let bit = detected.trailing_zeros();
+            let pin = (bank as u32 * 32 + bit) as u8;
+            if let Some(handler) = with_event_handlers(|handlers| handlers.get(pin as usize).copied().flatten()) {
+                handler(pin);
+            }
+            detected &= !(1 << bit);
+        }
+    }
+}
+
+/// Run `f` with the per-pin event handler table.
+#[cfg(not(test))]
+fn with_event_handlers<T>(f: impl FnOnce(&mut [Option<EventHandler>; pins::PIN_COUNT]) -> T) -> T {
+    static mut EVENT_HANDLERS: [Option<EventHandler>; pins::PIN_COUNT] = [None; pins::PIN_COUNT];
+    // Safety: single core. The table is only changed with IRQs masked, and the GPIO interrupt
+    // handler itself runs with IRQs masked, so the two never overlap.
+    f(unsafe { &mut *core::ptr::addr_of_mut!(EVENT_HANDLERS) })
+}
+
+/// Run `f` with the per-pin event handler table. Host tests get one table per test thread.
+#[cfg(test)]
+fn with_event_handlers<T>(f: impl FnOnce(&mut [Option<EventHandler>; pins::PIN_COUNT]) -> T) -> T {
+    extern crate std;
+    std::thread_local! {
+        static EVENT_HANDLERS: core::cell::RefCell<[Option<EventHandler>; pins::PIN_COUNT]> =
+            const { core::cell::RefCell::new([None; pins::PIN_COUNT]) };
+    }
+    EVENT_HANDLERS.with(|handlers| f(&mut handlers.borrow_mut()))
+}
+
+impl Drop for GpioPin {
+    fn drop(&mut self) {
+        // Leave nothing behind that could fire for the next owner of the pin
+        self.remove_event_handler();
+        self.disable_events();
+        pins::release(self.pin, self.owner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::gpio::GPIO_REGS_OFFSET;
+
+    const GPFSEL4: usize = GPIO_REGS_OFFSET + 0x10;
+    const GPSET0: usize = GPIO_REGS_OFFSET + 0x1C;
+    const GPSET1: usize = GPIO_REGS_OFFSET + 0x20;
+    const GPCLR1: usize = GPIO_REGS_OFFSET + 0x2C;
+    const GPLEV0: usize = GPIO_REGS_OFFSET + 0x34;
+    const GPLEV1: usize = GPIO_REGS_OFFSET + 0x38;
+    const GPEDS0: usize = GPIO_REGS_OFFSET + 0x40;
+    const GPFEN0: usize = GPIO_REGS_OFFSET + 0x58;
+    const GPPUD: usize = GPIO_REGS_OFFSET + 0x94;
+    const GPPUDCLK1: usize = GPIO_REGS_OFFSET + 0x9C;
+    const GPIO_PUP_PDN_CNTRL_REG2: usize = GPIO_REGS_OFFSET + 0xEC;
+
+    #[test]
+    fn set_output_writes_function_bits_of_the_pin_only() {
+        sim::reset();
+        sim::poke(GPFSEL4, 0b111 << 9); // GPIO43 configured as ALT3
+        GpioPin::new(42, "test").unwrap().set_output();
+        assert_eq!(sim::writes(GPFSEL4), [(0b001 << 6) | (0b111 << 9)]);
+    }
+
+    #[test]
+    fn set_high_and_low_write_the_pin_bit_of_the_right_bank() {
+        sim::reset();
+        let pin = GpioPin::new(42, "test").unwrap();
+        pin.set_high();
+        pin.set_low();
+        GpioPin::new(3, "test").unwrap().set_high();
+        assert_eq!(sim::writes(GPSET1), [1 << 10]);
+        assert_eq!(sim::writes(GPCLR1), [1 << 10]);
+        assert_eq!(sim::writes(GPSET0), [1 << 3]);
+    }
+
+    #[test]
+    fn set_input_and_level_reads_use_the_pin_bank() {
+        sim::reset();
+        sim::poke(GPFSEL4, 0b001 << 6); // GPIO42 configured as output
+        let button = GpioPin::new(42, "test").unwrap();
+        button.set_input();
+        assert_eq!(sim::peek(GPFSEL4), 0);
+        assert!(button.is_low());
+        sim::poke(GPLEV1, 1 << 10);
+        assert!(button.is_high());
+        sim::poke(GPLEV0, !(1 << 5));
+        assert!(GpioPin::new(5, "test").unwrap().is_low());
+    }
+
+    #[test]
+    fn signals_select_their_alternate_function_or_fail() {
+        sim::reset();
+        let pin = GpioPin::new(42, "test").unwrap();
+        assert_eq!(pin.set_signal("SDA1"), Err(PinError::UnsupportedSignal { pin: 42, signal: "SDA1" }));
+        assert_eq!(sim::writes(GPFSEL4), []);
+        pin.set_signal("GPCLK1").unwrap();
+        assert_eq!(sim::peek(GPFSEL4), 0b100 << 6); // ALT0
+        assert_eq!(
+            request_signals(&[(40, "TXD1"), (42, "RTS1")], "mini_uart"),
+            Err(PinError::AlreadyClaimed { pin: 42, owner: "test" })
+        );
+        assert_eq!(pins::owner_of(40), Ok(None));
+        let twice = [(14, "TXD0"), (15, "RXD0"), (14, "TXD1")];
+        assert_eq!(request_signals(&twice, "uart0"), Err(PinError::DuplicatePin(14)));
+        let too_many = [(14, "TXD0"); pins::PIN_COUNT + 1];
+        assert_eq!(request_signals(&too_many, "uart0"), Err(PinError::DuplicatePin(14)));
+        assert_eq!(pins::owner_of(14), Ok(None));
+    }
+
+    #[test]
+    fn set_pull_uses_the_mechanism_of_the_soc() {
+        sim::reset();
+        sim::poke(GPIO_PUP_PDN_CNTRL_REG2, 0b10 << 22); // GPIO43 pulled down
+        GpioPin::new(42, "test").unwrap().set_pull(Pull::Up);
+        match board::current().soc {
+            Soc::Bcm2711 => {
+                assert_eq!(sim::writes(GPIO_PUP_PDN_CNTRL_REG2), [(0b01 << 20) | (0b10 << 22)]);
+                assert_eq!(sim::writes(GPPUD), []);
+            }
+            Soc::Bcm2837 => {
+                assert_eq!(sim::writes(GPPUD), [0b10, 0b00]);
+                assert_eq!(sim::writes(GPPUDCLK1), [1 << 10, 0]);
+            }
+        }
+    }
+
+    #[test]
+    fn detected_events_run_the_handler_of_their_pin() {
+        use core::sync::atomic::{AtomicU8, Ordering};
+        static PRESSED: AtomicU8 = AtomicU8::new(0);
+        fn pressed(pin: u8) {
+            PRESSED.store(pin, Ordering::Relaxed);
+        }
+
+        sim::reset();
+        let button = GpioPin::new(17, "test").unwrap();
+        button.enable_event(Event::FallingEdge);
+        assert_eq!(sim::peek(GPFEN0), 1 << 17);
+        button.on_event(pressed);
+        assert!(interrupts::is_registered(irq::GPIO_BANK0));
+
+        sim::poke(GPEDS0, (1 << 17) | (1 << 3)); // Events on GPIO17 and on GPIO3 (no handler)
+        handle_interrupt();
+        assert_eq!(PRESSED.load(Ordering::Relaxed), 17);
+        assert_eq!(sim::last_write(GPEDS0), Some((1 << 17) | (1 << 3))); // Both cleared
+
+        drop(button);
+        assert_eq!(sim::peek(GPFEN0), 0);
+    }
+
+    #[test]
+    fn a_pin_has_a_single_owner_until_dropped() {
+        let led = GpioPin::new(42, "act_led").unwrap();
+        assert_eq!(
+            GpioPin::new(42, "blinker").err(),
+            Some(PinError::AlreadyClaimed { pin: 42, owner: "act_led" })
+        );
+        // Not even by the same owner: the two handles would fight, and dropping one frees the pin
+        assert_eq!(
+            GpioPin::new(42, "act_led").err(),
+            Some(PinError::AlreadyClaimed { pin: 42, owner: "act_led" })
+        );
+        assert_eq!(pins::owner_of(42).unwrap().unwrap().owner, "act_led");
+        drop(led);
+        assert!(GpioPin::new(42, "blinker").is_ok());
+    }
 }
@@ -0,0 +1,161 @@
+//! GPIO pad control
+//!
+//! Drive strength, slew rate limiting and input hysteresis can only be set per bank of pads
+//! (GPIO 0-27, 28-45, 46-53), not per pin. The password-protected write is handled here.
+//!
+//! # Example
+//! ```rust
+//! use drivers::gpio::pads::{self, DriveStrength, PadBank};
+//!
+//! // Long cable on the header pins: strongest drive, fast edges
+//! pads::set_drive_strength(PadBank::Gpio0To27, DriveStrength::Ma16);
+//! pads::set_slew_limited(PadBank::Gpio0To27, false);
+//! let config = pads::config(PadBank::of(10).unwrap());
+//! ```
+
+use crate::hal::registers::access::{FieldValue, ReadWrite};
+use crate::hal::registers::pads::{pads_regs, PADS};
+
+/// A bank of GPIO pads sharing one pad control register.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PadBank {
+    /// GPIO 0-27 (the 40-pin header).
+    Gpio0To27,
+    /// GPIO 28-45.
+    Gpio28To45,
+    /// GPIO 46-53.
+    Gpio46To53,
+}
+
+impl PadBank {
+    /// Pad bank of `pin` (`None` if the pin does not exist).
+    pub fn of(pin: u8) -> Option<PadBank> {
+        match pin {
+            0..=27 => Some(PadBank::Gpio0To27),
+            28..=45 => Some(PadBank::Gpio28To45),
+            46..=53 => Some(PadBank::Gpio46To53),
+            _ => None,
+        }
+    }
+
+    fn register(self) -> &'static ReadWrite<PADS::Register> {
+        let regs = pads_regs();
+        match self {
+            PadBank::Gpio0To27 => &regs.gpio_0_27,
+            PadBank::Gpio28To45 => &regs.gpio_28_45,
+            PadBank::Gpio46To53 => &regs.gpio_46_53,
+        }
+    }
+}
+
+/// Output drive strength, in mA.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DriveStrength {
+    Ma2 = 0,
+    Ma4 = 1,
+    Ma6 = 2,
+    Ma8 = 3, // Reset default
+    Ma10 = 4,
+    Ma12 = 5,
+    Ma14 = 6,
+    Ma16 = 7,
+}
+
+impl DriveStrength {
+    /// Drive strength for a current in mA (2, 4, ... 16).
+    pub fn from_milliamps(milliamps: u8) -> Option<DriveStrength> {
+        const ALL: [DriveStrength; 8] = [
+            DriveStrength::Ma2,
+            DriveStrength::Ma4,
+            DriveStrength::Ma6,
+            DriveStrength::Ma8,
+            DriveStrength::Ma10,
+            DriveStrength::Ma12,
+            DriveStrength::Ma14,
+            DriveStrength::Ma16,
+        ];
+        match milliamps {
+            2..=16 if milliamps.is_multiple_of(2) => Some(ALL[(milliamps / 2 - 1) as usize]),
+            _ => None,
+        }
+    }
+
+    /// Current in mA.
+    pub fn milliamps(self) -> u8 {
+        (self as u8 + 1) * 2
+    }
+}
+
+/// Configuration of one pad bank.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PadConfig {
+    pub drive: DriveStrength,
+    /// Limit the slew rate (slower edges, less ringing and interference on long cables).
+    pub slew_limited: bool,
+    /// Input hysteresis (Schmitt trigger), for noisy or slow input signals.
+    pub hysteresis: bool,
+}
+
+/// Current configuration of a pad bank.
+pub fn config(bank: PadBank) -> PadConfig {
+    let value = bank.register().get();
+    PadConfig {
+        drive: DriveStrength::from_milliamps((PADS::DRIVE.read(value) as u8 + 1) * 2).unwrap_or(DriveStrength::Ma8),
+        slew_limited: !PADS::SLEW.is_set(value),
+        hysteresis: PADS::HYST.is_set(value),
+    }
+}
+
+/// Apply a complete configuration to a pad bank.
+pub fn configure(bank: PadBank, config: PadConfig) {
+    write(
+        bank,
+        PADS::DRIVE.val(config.drive as u32)
+            + PADS::SLEW.val(!config.slew_limited as u32)
+            + PADS::HYST.val(config.hysteresis as u32),
+    );
+}
+
+/// Set the drive strength of a pad bank, keeping its other settings.
+pub fn set_drive_strength(bank: PadBank, drive: DriveStrength) {
+    write(bank, PADS::DRIVE.val(drive as u32));
+}
+
+/// Enable or disable slew rate limiting of a pad bank, keeping its other settings.
+pub fn set_slew_limited(bank: PadBank, limited: bool) {
+    write(bank, PADS::SLEW.val(!limited as u32));
+}
+
+/// Enable or disable input hysteresis of a pad bank, keeping its other settings.
+pub fn set_hysteresis(bank: PadBank, enabled: bool) {
+    write(bank, PADS::HYST.val(enabled as u32));
+}
+
+/// Read-modify-write of a pad control register with the password (writes without it are ignored).
+fn write(bank: PadBank, value: FieldValue<PADS::Register>) {
+    let register = bank.register();
+    let settings = register.get() & 0x1F; // DRIVE, HYST and SLEW: drop whatever reads back above
+    register.set(value.modify(settings) | PADS::PASSWD::Password.value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::pads::PADS_REGS_OFFSET;
+
+    #[test]
+    fn writes_carry_the_password_and_keep_the_other_settings() {
+        sim::reset();
+        sim::poke(PADS_REGS_OFFSET, 0x1B); // Reset value: 8 mA, hysteresis, fast slew
+        set_drive_strength(PadBank::Gpio0To27, DriveStrength::Ma16);
+        assert_eq!(sim::last_write(PADS_REGS_OFFSET), Some(0x5A00_001F));
+        set_slew_limited(PadBank::Gpio0To27, true);
+        assert_eq!(
+            config(PadBank::Gpio0To27),
+            PadConfig { drive: DriveStrength::Ma16, slew_limited: true, hysteresis: true }
+        );
+        assert_eq!(DriveStrength::from_milliamps(12), Some(DriveStrength::Ma12));
+        assert_eq!(DriveStrength::from_milliamps(3), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Multi-pin GPIO port
+//!
+//! A `GpioPort` owns a set of pins of one register [`Bank`] and drives or reads all of them with
+//! single register accesses, so the pins of a parallel bus change together instead of one after
+//! the other. Masks and values use the bit layout of the bank registers: bit n is pin
+//! `bank.first_pin() + n`.
+//!
+//! # Example
+//! ```rust
+//! use drivers::gpio::{Bank, GpioPort};
+//!
+//! // 8-bit LCD data bus on GPIO4-11
+//! let bus = GpioPort::new(Bank::Bank0, 0xFF << 4, "lcd_data")?;
+//! bus.set_output();
+//! bus.write(0xA5 << 4);          // One GPSET0 + one GPCLR0 access
+//! bus.write_masked(0, 0x0F << 4); // Clear only the lower nibble
+//! let levels = bus.read() >> 4;   // One GPLEV0 access
+//! ```
+
+use super::{write_function, Function};
+use crate::drivers::pins::{self, PinError, PIN_COUNT};
+use crate::hal::registers::gpio::gpio_regs;
+
+/// Register bank of the GPIO block: GPFSEL/GPSET/GPCLR/GPLEV registers 0 or 1.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bank {
+    /// GPIO0-31
+    Bank0,
+    /// GPIO32-53
+    Bank1,
+}
+
+impl Bank {
+    /// Pin number of bit 0 of the bank registers.
+    pub const fn first_pin(self) -> u8 {
+        match self {
+            Bank::Bank0 => 0,
+            Bank::Bank1 => 32,
+        }
+    }
+}
+
+/// A set of GPIO pins of one register bank, accessed together.
+pub struct GpioPort {
+    bank: Bank,
+    mask: u32,
+    owner: &'static str,
+}
+
+impl GpioPort {
+    /// Claim the pins in `mask` of register `bank` for `owner`.
+    /// Fails if one of the pins does not exist or is already owned (even by `owner`); nothing is
+    /// claimed then.
+    pub fn new(bank: Bank, mask: u32, owner: &'static str) -> Result<Self, PinError> {
+        let port = GpioPort { bank, mask, owner };
+        let mut claims = [(0, Function::Input); 32];
+        let mut count = 0;
+        for pin in port.pins() {
+            if pin as usize >= PIN_COUNT {
+                return Err(PinError::InvalidPin(pin));
+            }
+            claims[count] = (pin, Function::Input);
+            count += 1;
+        }
+        pins::claim_all_new(&claims[..count], owner)?;
+        Ok(port)
+    }
+
+    /// Pins of this port, as a mask of the bank registers.
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// Configure all pins of the port as inputs.
+    pub fn set_input(&self) {
+        self.set_function(Function::Input);
+    }
+
+    /// Configure all pins of the port as outputs.
+    pub fn set_output(&self) {
+        self.set_function(Function::Output);
+    }
+
+    /// Drive the pins in `mask` high (one GPSETn write). Bits outside the port are ignored.
+    pub fn set(&self, mask: u32) {
+        let regs = gpio_regs();
+        let set = if self.bank == Bank::Bank0 { &regs.gpset0 } else { &regs.gpset1 };
+        set.set(mask & self.mask);
+    }
+
+    /// Drive the pins in `mask` low (one GPCLRn write). Bits outside the port are ignored.
+    pub fn clear(&self, mask: u32) {
+        let regs = gpio_regs();
+        let clear = if self.bank == Bank::Bank0 { &regs.gpclr0 } else { &regs.gpclr1 };
+        clear.set(mask & self.mask);
+    }
+
+    /// Read the levels of all pins of the port (one GPLEVn read). Bits outside the port are zero.
+    pub fn read(&self) -> u32 {
+        let regs = gpio_regs();
+        let level = if self.bank == Bank::Bank0 { regs.gplev0.get() } else { regs.gplev1.get() };
+        level & self.mask
+    }
+
+    /// Drive every pin of the port to the level of its bit in `value`.
+    pub fn write(&self, value: u32) {
+        self.write_masked(value, self.mask);
+    }
+
+    /// Drive the pins in `mask` to the level of their bit in `value`, leaving the others alone.
+    /// This is exactly one set and one clear access: pins going high change first.
+    pub fn write_masked(&self, value: u32, mask: u32) {
+        let mask = mask & self.mask;
+        if value & mask != 0 {
+            self.set(value & mask);
+        }
+        if !value & mask != 0 {
+            self.clear(!value & mask);
+        }
+    }
+
+    fn set_function(&self, function: Function) {
+        for pin in self.pins() {
+            // Cannot fail: we own the pin, and only record its new function
+            let _ = pins::claim(pin, self.owner, function);
+            write_function(pin, function);
+        }
+    }
+
+    /// Pin numbers of this port.
+    fn pins(&self) -> impl Iterator<Item = u8> {
+        let (first, mask) = (self.bank.first_pin(), self.mask);
+        (0..32u8).filter(move |bit| mask & (1 << bit) != 0).map(move |bit| first + bit)
+    }
+}
+
+impl Drop for GpioPort {
+    fn drop(&mut self) {
+        for pin in self.pins() {
+            pins::release(pin, self.owner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::gpio::GPIO_REGS_OFFSET;
+
+    const GPSET0: usize = GPIO_REGS_OFFSET + 0x1C;
+    const GPCLR0: usize = GPIO_REGS_OFFSET + 0x28;
+    const GPLEV1: usize = GPIO_REGS_OFFSET + 0x38;
+
+    #[test]
+    fn masked_writes_are_one_set_and_one_clear_access() {
+        sim::reset();
+        let bus = GpioPort::new(Bank::Bank0, 0xFF << 4, "test").unwrap();
+        bus.write(0xA5 << 4);
+        assert_eq!(sim::writes(GPSET0), [0xA5 << 4]);
+        assert_eq!(sim::writes(GPCLR0), [0x5A << 4]);
+        bus.write_masked(0xFFFF_FFFF, 0x3 << 3); // GPIO3 does not belong to the port
+        assert_eq!(sim::last_write(GPSET0), Some(0x1 << 4));
+        assert_eq!(sim::writes(GPCLR0).len(), 1);
+    }
+
+    #[test]
+    fn ports_claim_all_their_pins_or_none() {
+        sim::reset();
+        sim::poke(GPLEV1, 0xFFFF_FFFF);
+        let port = GpioPort::new(Bank::Bank1, 0b11, "test").unwrap();
+        assert_eq!(port.read(), 0b11);
+        assert!(GpioPort::new(Bank::Bank1, 0b110, "other").is_err());
+        assert_eq!(pins::owner_of(34), Ok(None));
+        assert_eq!(GpioPort::new(Bank::Bank1, 1 << 22, "other").err(), Some(PinError::InvalidPin(54)));
+    }
+}
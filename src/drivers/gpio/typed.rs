@@ -0,0 +1,209 @@
+//! Type-state GPIO pins
+//!
+//! `Pin<N, MODE>` carries the pin number and its mode in the type:
+//! - an out-of-range pin number (`Pin<60, _>`) is a compile error,
+//! - `set_high` only exists on `Pin<N, Output>`, `is_high` on `Pin<N, Input>`,
+//! - changing the mode consumes the pin and returns it in the new mode.
+//!
+//! Use [`GpioPin`] instead when the pin number is only known at run time (e.g. the ACT LED pin of
+//! the detected board). Both claim their pin in the pin registry, so they cannot fight each other.
+//!
+//! # Example
+//! ```rust
+//! use drivers::gpio::{Input, Output, Pin};
+//!
+//! let button = Pin::<17, Input>::take("button")?;
+//! let led = Pin::<18, Input>::take("led")?.into_output();
+//! led.set_high();
+//! if button.is_low() { led.set_low(); }
+//! // button.set_high();                    // Does not compile: `button` is an input
+//! // let bad = Pin::<60, Input>::take("x"); // Does not compile: there is no GPIO60
+//! let pwm = Pin::<18, Input>::take("pwm")?.into_alt::<5>(); // PWM0_0
+//! ```
+
+use core::marker::PhantomData;
+
+use super::{Event, EventHandler, Function, GpioPin, Pull};
+use crate::drivers::pinmux;
+use crate::drivers::pins::{PinError, PIN_COUNT};
+
+/// Mode of a pin configured as input.
+pub struct Input;
+
+/// Mode of a pin configured as output.
+pub struct Output;
+
+/// Mode of a pin configured as alternate function `F` (ALT0-ALT5).
+pub struct Alt<const F: u8>;
+
+impl<const F: u8> Alt<F> {
+    /// Evaluated at compile time: ALT0-ALT5 only.
+    const VALID: () = assert!(F < 6, "alternate function out of range (ALT0-ALT5)");
+
+    /// Function select value of this alternate function.
+    const FUNCTION: Function = match F {
+        0 => Function::Alt0,
+        1 => Function::Alt1,
+        2 => Function::Alt2,
+        3 => Function::Alt3,
+        4 => Function::Alt4,
+        _ => Function::Alt5,
+    };
+}
+
+/// GPIO pin `N` in mode `MODE` ([`Input`], [`Output`] or [`Alt<F>`]).
+pub struct Pin<const N: u8, MODE> {
+    pin: GpioPin,
+    mode: PhantomData<MODE>,
+}
+
+impl<const N: u8, MODE> Pin<N, MODE> {
+    /// Evaluated at compile time: rejects pins that do not exist.
+    const VALID: () = assert!((N as usize) < PIN_COUNT, "GPIO pin number out of range (0-53)");
+
+    /// Configure the pin as input.
+    pub fn into_input(self) -> Pin<N, Input> {
+        self.pin.set_input();
+        self.into_mode()
+    }
+
+    /// Configure the pin as output.
+    pub fn into_output(self) -> Pin<N, Output> {
+        self.pin.set_output();
+        self.into_mode()
+    }
+
+    /// Configure the pin as alternate function `F` (e.g. `into_alt::<0>()` for ALT0).
+    pub fn into_alt<const F: u8>(self) -> Pin<N, Alt<F>> {
+        #[allow(clippy::let_unit_value)]
+        let () = Alt::<F>::VALID;
+        self.pin.set_function(Alt::<F>::FUNCTION);
+        self.into_mode()
+    }
+
+    /// Enable the pull-up or pull-down resistor of this pin, or disable both.
+    pub fn set_pull(&self, pull: Pull) {
+        self.pin.set_pull(pull);
+    }
+
+    /// Give up the type-state and keep using the pin through the run-time API.
+    pub fn into_dynamic(self) -> GpioPin {
+        self.pin
+    }
+
+    fn into_mode<M>(self) -> Pin<N, M> {
+        Pin { pin: self.pin, mode: PhantomData }
+    }
+}
+
+impl<const N: u8> Pin<N, Input> {
+    /// Claim pin `N` for `owner` and configure it as input.
+    /// Fails if the pin is already owned, even by `owner` (another handle).
+    pub fn take(owner: &'static str) -> Result<Self, PinError> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        let pin = GpioPin::new(N, owner)?;
+        pin.set_input();
+        Ok(Pin { pin, mode: PhantomData })
+    }
+
+    /// Read the level of the pin: `true` if it is high.
+    pub fn is_high(&self) -> bool {
+        self.pin.is_high()
+    }
+
+    /// Read the level of the pin: `true` if it is low.
+    pub fn is_low(&self) -> bool {
+        self.pin.is_low()
+    }
+
+    /// Start detecting `event` on this pin.
+    pub fn enable_event(&self, event: Event) {
+        self.pin.enable_event(event);
+    }
+
+    /// Stop detecting `event` on this pin.
+    pub fn disable_event(&self, event: Event) {
+        self.pin.disable_event(event);
+    }
+
+    /// Check whether an enabled event was detected on this pin.
+    pub fn event_detected(&self) -> bool {
+        self.pin.event_detected()
+    }
+
+    /// Clear the detected event of this pin.
+    pub fn clear_event(&self) {
+        self.pin.clear_event();
+    }
+
+    /// Run `handler` from the GPIO interrupt whenever an enabled event is detected on this pin.
+    pub fn on_event(&self, handler: EventHandler) {
+        self.pin.on_event(handler);
+    }
+}
+
+impl<const N: u8> Pin<N, Output> {
+    /// Drive the pin high (logic 1).
+    pub fn set_high(&self) {
+        self.pin.set_high();
+    }
+
+    /// Drive the pin low (logic 0).
+    pub fn set_low(&self) {
+        self.pin.set_low();
+    }
+
+    /// Invert the driven level.
+    pub fn toggle(&self) {
+        if self.is_set_high() {
+            self.set_low();
+        } else {
+            self.set_high();
+        }
+    }
+
+    /// Check whether the pin is driven high.
+    pub fn is_set_high(&self) -> bool {
+        self.pin.is_set_high()
+    }
+
+    /// Check whether the pin is driven low.
+    pub fn is_set_low(&self) -> bool {
+        self.pin.is_set_low()
+    }
+}
+
+impl<const N: u8, const F: u8> Pin<N, Alt<F>> {
+    /// Signal the pin carries in this alternate function (e.g. "TXD0"), if known.
+    pub fn signal(&self) -> Option<&'static str> {
+        pinmux::signal(N, Alt::<F>::FUNCTION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::gpio::GPIO_REGS_OFFSET;
+
+    const GPFSEL1: usize = GPIO_REGS_OFFSET + 0x04;
+    const GPSET0: usize = GPIO_REGS_OFFSET + 0x1C;
+    const GPCLR0: usize = GPIO_REGS_OFFSET + 0x28;
+
+    #[test]
+    fn mode_changes_write_the_function_select_bits() {
+        sim::reset();
+        let led = Pin::<17, Input>::take("test").unwrap().into_output();
+        assert_eq!(sim::peek(GPFSEL1), 0b001 << 21);
+        led.set_high();
+        led.set_low();
+        assert_eq!(sim::writes(GPSET0), [1 << 17]);
+        assert_eq!(sim::writes(GPCLR0), [1 << 17]);
+
+        let tx = Pin::<14, Input>::take("test").unwrap().into_alt::<0>();
+        assert_eq!(sim::peek(GPFSEL1), (0b001 << 21) | (0b100 << 12));
+        assert_eq!(tx.signal(), Some("TXD0"));
+        assert!(Pin::<14, Input>::take("other").is_err());
+    }
+}
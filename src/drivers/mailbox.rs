@@ -0,0 +1,127 @@
+//! VideoCore mailbox property interface.
+//!
+//! The firmware running on the VideoCore answers "property tag" requests sent through the mailbox:
+//! board revision, clock rates, memory split and so on. A request is a 16-byte aligned buffer in RAM
+//! whose address is written into the mailbox; the firmware overwrites the buffer with the response.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::mailbox;
+//!
+//! if let Some(revision) = mailbox::board_revision() {
+//!     println!("Board revision: 0x{:08X}", revision);
+//! }
+//! let uart_clock = mailbox::clock_rate(mailbox::clock::UART);
+//! ```
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::hal::registers::mailbox::{mailbox_regs, MBOX_DATA, MBOX_STATUS};
+
+/// Property tags used by the kernel.
+pub mod tag {
+    /// Get board revision. Response: 1 word (revision code).
+    pub const GET_BOARD_REVISION: u32 = 0x0001_0002;
+    /// Get clock rate. Request: clock id. Response: clock id, rate in Hz.
+    pub const GET_CLOCK_RATE: u32 = 0x0003_0002;
+}
+
+/// Clock ids for [`tag::GET_CLOCK_RATE`].
+pub mod clock {
+    /// PL011 reference clock (UARTCLK).
+    pub const UART: u32 = 2;
+    /// VPU core clock (Mini UART, SPI, I2C...).
+    pub const CORE: u32 = 4;
+}
+
+/// Mailbox channel for property tags (ARM to VideoCore).
+const CHANNEL_PROPERTY: u32 = 8;
+/// Request code in the buffer header.
+const REQUEST: u32 = 0;
+/// Response code in the buffer header when the firmware processed the request.
+const RESPONSE_SUCCESS: u32 = 0x8000_0000;
+/// Set in the tag's size/code word when the firmware answered the tag.
+const TAG_RESPONSE: u32 = 0x8000_0000;
+/// Maximum number of value words supported by [`property`].
+const MAX_VALUE_WORDS: usize = 8;
+/// Number of status polls before giving up (e.g. when there is no firmware to answer).
+const TIMEOUT_POLLS: u32 = 1_000_000;
+
+/// Property buffer. The firmware requires 16-byte alignment (the low 4 bits carry the channel).
+#[repr(C, align(16))]
+struct Buffer([u32; 6 + MAX_VALUE_WORDS]);
+
+/// Send a single property tag to the firmware and wait for the answer.
+///
+/// `value` holds the request words on entry and the response words on return
+/// (at most 8 words). Returns `None` if the firmware did not answer the tag.
+pub fn property(tag: u32, value: &mut [u32]) -> Option<()> {
+    if value.len() > MAX_VALUE_WORDS {
+        return None;
+    }
+    let mut buffer = Buffer([0; 6 + MAX_VALUE_WORDS]);
+    let words = 6 + value.len();
+    buffer.0[0] = (words * 4) as u32; // Total buffer size in bytes
+    buffer.0[1] = REQUEST;
+    buffer.0[2] = tag;
+    buffer.0[3] = (value.len() * 4) as u32; // Value buffer size in bytes
+    buffer.0[4] = 0; // Request code
+    buffer.0[5..words - 1].copy_from_slice(value);
+    buffer.0[words - 1] = 0; // End tag
+
+    // The MMU and caches are off, so the ARM physical address is also what the VideoCore sees
+    // and no cache maintenance is needed. Make sure the buffer is written before the mailbox is.
+    let address = buffer.0.as_mut_ptr() as usize as u32;
+    compiler_fence(Ordering::SeqCst);
+
+    let mailbox = mailbox_regs();
+    wait_until(|| !mailbox.write_status.is_set(MBOX_STATUS::FULL))?;
+    mailbox.write.set((address & !0xF) | CHANNEL_PROPERTY);
+    loop {
+        wait_until(|| !mailbox.status.is_set(MBOX_STATUS::EMPTY))?;
+        let answer = mailbox.read.get();
+        if MBOX_DATA::CHANNEL.read(answer) == CHANNEL_PROPERTY && answer & !0xF == address & !0xF {
+            break; // Answer to our request (anything else is a stale message, skip it)
+        }
+    }
+    compiler_fence(Ordering::SeqCst);
+
+    // The firmware wrote the buffer behind the compiler's back: read it back volatile.
+    let read = |index: usize| unsafe { core::ptr::read_volatile(&buffer.0[index]) };
+    if read(1) != RESPONSE_SUCCESS || read(4) & TAG_RESPONSE == 0 {
+        return None;
+    }
+    for (index, word) in value.iter_mut().enumerate() {
+        *word = read(5 + index);
+    }
+    Some(())
+}
+
+/// Board revision code (see the "Raspberry Pi revision codes" documentation).
+pub fn board_revision() -> Option<u32> {
+    let mut value = [0u32; 1];
+    property(tag::GET_BOARD_REVISION, &mut value)?;
+    Some(value[0])
+}
+
+/// Current rate of a firmware managed clock in Hz (see [`clock`]). `None` if unknown or zero.
+pub fn clock_rate(clock_id: u32) -> Option<u32> {
+    let mut value = [clock_id, 0];
+    property(tag::GET_CLOCK_RATE, &mut value)?;
+    if value[1] == 0 {
+        None
+    } else {
+        Some(value[1])
+    }
+}
+
+/// Poll `condition` until it holds, giving up after [`TIMEOUT_POLLS`] attempts.
+fn wait_until(condition: impl Fn() -> bool) -> Option<()> {
+    for _ in 0..TIMEOUT_POLLS {
+        if condition() {
+            return Some(());
+        }
+        core::hint::spin_loop();
+    }
+    None
+}
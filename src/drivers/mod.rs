@@ -1,2 +1,10 @@
+#[cfg(feature = "embedded-hal")]
+pub mod ehal;
 pub mod gpio;
-pub mod uart;
\ No newline at end of file
+pub mod mailbox;
+pub mod peripherals;
+pub mod pinmux;
+pub mod pins;
+pub mod timer;
+pub mod uart;
+pub mod watchdog;
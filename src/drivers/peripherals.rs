@@ -0,0 +1,90 @@
+//! Peripheral ownership
+//!
+//! `Peripherals::take()` hands out one token per peripheral, exactly once. A driver's `init`
+//! consumes the token, so a peripheral cannot be initialized twice (or from two places).
+//! Drivers also claim their pins in the pin registry (`drivers::pins`): `uart0` and `mini_uart`
+//! both use GPIO14/15, so only the first of the two to be initialized gets them.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::peripherals::Peripherals;
+//!
+//! let peripherals = Peripherals::take().unwrap();
+//! uart0::init(peripherals.uart0, &UartConfig::default())?; // Claims GPIO14/15
+//! mini_uart::init(peripherals.mini_uart, &UartConfig::default()); // Err(Pin(AlreadyClaimed { pin: 14, owner: "uart0" }))
+//! assert!(Peripherals::take().is_none()); // Only once
+//! ```
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once the peripherals have been taken.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Ownership token of the PL011 UART (UART0).
+pub struct Uart0 {
+    _private: (),
+}
+
+/// Ownership token of the Mini UART (UART1).
+pub struct MiniUart {
+    _private: (),
+}
+
+/// Ownership token of the PL011 UART2 (BCM2711 only).
+pub struct Uart2 {
+    _private: (),
+}
+
+/// Ownership token of the PL011 UART3 (BCM2711 only).
+pub struct Uart3 {
+    _private: (),
+}
+
+/// Ownership token of the PL011 UART4 (BCM2711 only).
+pub struct Uart4 {
+    _private: (),
+}
+
+/// Ownership token of the PL011 UART5 (BCM2711 only).
+pub struct Uart5 {
+    _private: (),
+}
+
+/// All peripheral tokens.
+pub struct Peripherals {
+    pub uart0: Uart0,
+    pub mini_uart: MiniUart,
+    pub uart2: Uart2,
+    pub uart3: Uart3,
+    pub uart4: Uart4,
+    pub uart5: Uart5,
+}
+
+impl Peripherals {
+    /// Take the peripherals. Returns `None` if they were already taken.
+    pub fn take() -> Option<Self> {
+        // Plain load + store instead of a compare-and-swap: we run on a single core, and the
+        // exclusive load/store instructions behind a CAS need the MMU (caches) to be enabled.
+        if TAKEN.load(Ordering::Relaxed) {
+            return None;
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+        Some(unsafe { Self::steal() })
+    }
+
+    /// Create the peripheral tokens regardless of whether they were already taken.
+    ///
+    /// # Safety
+    /// The caller must make sure no two users drive the same peripheral (e.g. host tests, which
+    /// each run against their own simulated registers).
+    pub unsafe fn steal() -> Self {
+        Peripherals {
+            uart0: Uart0 { _private: () },
+            mini_uart: MiniUart { _private: () },
+            uart2: Uart2 { _private: () },
+            uart3: Uart3 { _private: () },
+            uart4: Uart4 { _private: () },
+            uart5: Uart5 { _private: () },
+        }
+    }
+}
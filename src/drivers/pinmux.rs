@@ -0,0 +1,121 @@
+//! GPIO alternate function table (BCM2711)
+//!
+//! Names the signal each pin carries in each alternate function, as listed in the
+//! "Alternative Function Assignments" table of the BCM2711 ARM Peripherals datasheet.
+//! Drivers request their pins by signal name instead of hard-coding ALTn numbers.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::pinmux;
+//!
+//! pinmux::find(14, "TXD0");      // Ok(Function::Alt0)
+//! pinmux::find(14, "TXD1");      // Ok(Function::Alt5) (Mini UART)
+//! pinmux::find(14, "SDA1");      // Err(PinError::UnsupportedSignal { pin: 14, signal: "SDA1" })
+//! pinmux::signal(2, Function::Alt0); // Some("SDA1")
+//! ```
+//!
+//! Pins 0-27 (the 40-pin header) match the BCM2835/BCM2837 assignments except for the extra
+//! UART2-5, SPI3-6 and I2C3-6 controllers of the BCM2711, which do not exist on older boards.
+//! Pins 46-53 are used on-board (SD card, eMMC) and have no entries.
+
+use super::gpio::Function;
+use super::pins::PinError;
+
+/// Alternate function order of the table columns.
+const ALTERNATES: [Function; 6] = [
+    Function::Alt0,
+    Function::Alt1,
+    Function::Alt2,
+    Function::Alt3,
+    Function::Alt4,
+    Function::Alt5,
+];
+
+/// Signal of every pin in ALT0..ALT5. An empty string means reserved / not connected.
+#[rustfmt::skip]
+const BCM2711_FUNCTIONS: [[&str; 6]; 46] = [
+    // ALT0         ALT1       ALT2         ALT3              ALT4               ALT5
+    ["SDA0",        "SA5",     "PCLK",      "SPI3_CE0_N",     "TXD2",            "SDA6"],         // GPIO0
+    ["SCL0",        "SA4",     "DE",        "SPI3_MISO",      "RXD2",            "SCL6"],         // GPIO1
+    ["SDA1",        "SA3",     "LCD_VSYNC", "SPI3_MOSI",      "CTS2",            "SDA3"],         // GPIO2
+    ["SCL1",        "SA2",     "LCD_HSYNC", "SPI3_SCLK",      "RTS2",            "SCL3"],         // GPIO3
+    ["GPCLK0",      "SA1",     "DPI_D0",    "SPI4_CE0_N",     "TXD3",            "SDA3"],         // GPIO4
+    ["GPCLK1",      "SA0",     "DPI_D1",    "SPI4_MISO",      "RXD3",            "SCL3"],         // GPIO5
+    ["GPCLK2",      "SOE_N",   "DPI_D2",    "SPI4_MOSI",      "CTS3",            "SDA4"],         // GPIO6
+    ["SPI0_CE1_N",  "SWE_N",   "DPI_D3",    "SPI4_SCLK",      "RTS3",            "SCL4"],         // GPIO7
+    ["SPI0_CE0_N",  "SD0",     "DPI_D4",    "BSCSL_CE_N",     "TXD4",            "SDA4"],         // GPIO8
+    ["SPI0_MISO",   "SD1",     "DPI_D5",    "BSCSL_MISO",     "RXD4",            "SCL4"],         // GPIO9
+    ["SPI0_MOSI",   "SD2",     "DPI_D6",    "BSCSL_SDA_MOSI", "CTS4",            "SDA5"],         // GPIO10
+    ["SPI0_SCLK",   "SD3",     "DPI_D7",    "BSCSL_SCL_SCLK", "RTS4",            "SCL5"],         // GPIO11
+    ["PWM0_0",      "SD4",     "DPI_D8",    "SPI5_CE0_N",     "TXD5",            "SDA5"],         // GPIO12
+    ["PWM0_1",      "SD5",     "DPI_D9",    "SPI5_MISO",      "RXD5",            "SCL5"],         // GPIO13
+    ["TXD0",        "SD6",     "DPI_D10",   "SPI5_MOSI",      "CTS5",            "TXD1"],         // GPIO14
+    ["RXD0",        "SD7",     "DPI_D11",   "SPI5_SCLK",      "RTS5",            "RXD1"],         // GPIO15
+    ["",            "SD8",     "DPI_D12",   "CTS0",           "SPI1_CE2_N",      "CTS1"],         // GPIO16
+    ["",            "SD9",     "DPI_D13",   "RTS0",           "SPI1_CE1_N",      "RTS1"],         // GPIO17
+    ["PCM_CLK",     "SD10",    "DPI_D14",   "SPI6_CE0_N",     "SPI1_CE0_N",      "PWM0_0"],       // GPIO18
+    ["PCM_FS",      "SD11",    "DPI_D15",   "SPI6_MISO",      "SPI1_MISO",       "PWM0_1"],       // GPIO19
+    ["PCM_DIN",     "SD12",    "DPI_D16",   "SPI6_MOSI",      "SPI1_MOSI",       "GPCLK0"],       // GPIO20
+    ["PCM_DOUT",    "SD13",    "DPI_D17",   "SPI6_SCLK",      "SPI1_SCLK",       "GPCLK1"],       // GPIO21
+    ["SD0_CLK",     "SD14",    "DPI_D18",   "SD1_CLK",        "ARM_TRST",        "SDA6"],         // GPIO22
+    ["SD0_CMD",     "SD15",    "DPI_D19",   "SD1_CMD",        "ARM_RTCK",        "SCL6"],         // GPIO23
+    ["SD0_DAT0",    "SD16",    "DPI_D20",   "SD1_DAT0",       "ARM_TDO",         "SPI3_CE1_N"],   // GPIO24
+    ["SD0_DAT1",    "SD17",    "DPI_D21",   "SD1_DAT1",       "ARM_TCK",         "SPI4_CE1_N"],   // GPIO25
+    ["SD0_DAT2",    "TE0",     "DPI_D22",   "SD1_DAT2",       "ARM_TDI",         "SPI5_CE1_N"],   // GPIO26
+    ["SD0_DAT3",    "TE1",     "DPI_D23",   "SD1_DAT3",       "ARM_TMS",         "SPI6_CE1_N"],   // GPIO27
+    ["SDA0",        "SA5",     "PCM_CLK",   "",               "MII_A_RX_ERR",    "RGMII_MDIO"],   // GPIO28
+    ["SCL0",        "SA4",     "PCM_FS",    "",               "MII_A_TX_ERR",    "RGMII_MDC"],    // GPIO29
+    ["",            "SA3",     "PCM_DIN",   "CTS0",           "MII_A_CRS",       "CTS1"],         // GPIO30
+    ["",            "SA2",     "PCM_DOUT",  "RTS0",           "MII_A_COL",       "RTS1"],         // GPIO31
+    ["GPCLK0",      "SA1",     "",          "TXD0",           "SD_CARD_PRES",    "TXD1"],         // GPIO32
+    ["",            "SA0",     "",          "RXD0",           "SD_CARD_WRPROT",  "RXD1"],         // GPIO33
+    ["GPCLK0",      "SOE_N",   "",          "SD1_CLK",        "SD_CARD_LED",     "RGMII_IRQ"],    // GPIO34
+    ["SPI0_CE1_N",  "SWE_N",   "",          "SD1_CMD",        "RGMII_START_STOP", ""],            // GPIO35
+    ["SPI0_CE0_N",  "SD0",     "TXD0",      "SD1_DAT0",       "RGMII_RX_OK",     "MII_A_RX_ERR"], // GPIO36
+    ["SPI0_MISO",   "SD1",     "RXD0",      "SD1_DAT1",       "RGMII_MDIO",      "MII_A_TX_ERR"], // GPIO37
+    ["SPI0_MOSI",   "SD2",     "RTS0",      "SD1_DAT2",       "RGMII_MDC",       "MII_A_CRS"],    // GPIO38
+    ["SPI0_SCLK",   "SD3",     "CTS0",      "SD1_DAT3",       "RGMII_IRQ",       "MII_A_COL"],    // GPIO39
+    ["PWM1_0",      "SD4",     "",          "SD1_DAT4",       "SPI0_MISO",       "TXD1"],         // GPIO40
+    ["PWM1_1",      "SD5",     "",          "SD1_DAT5",       "SPI0_MOSI",       "RXD1"],         // GPIO41
+    ["GPCLK1",      "SD6",     "",          "SD1_DAT6",       "SPI0_SCLK",       "RTS1"],         // GPIO42
+    ["GPCLK2",      "SD7",     "",          "SD1_DAT7",       "SPI0_CE0_N",      "CTS1"],         // GPIO43
+    ["GPCLK1",      "SDA0",    "SDA1",      "",               "SPI0_CE1_N",      "SD_CARD_VOLT"], // GPIO44
+    ["PWM0_1",      "SCL0",    "SCL1",      "",               "SPI0_CE2_N",      "SD_CARD_PWR0"], // GPIO45
+];
+
+/// Signal carried by `pin` in the given function (`None` for input/output or unassigned).
+pub fn signal(pin: u8, function: Function) -> Option<&'static str> {
+    let row = BCM2711_FUNCTIONS.get(pin as usize)?;
+    let column = ALTERNATES.iter().position(|&alternate| alternate == function)?;
+    Some(row[column]).filter(|name| !name.is_empty())
+}
+
+/// Alternate function in which `pin` carries `signal`.
+pub fn find(pin: u8, signal: &'static str) -> Result<Function, PinError> {
+    if pin as usize >= super::pins::PIN_COUNT {
+        return Err(PinError::InvalidPin(pin));
+    }
+    BCM2711_FUNCTIONS
+        .get(pin as usize)
+        .and_then(|row| row.iter().position(|&name| name == signal))
+        .map(|column| ALTERNATES[column])
+        .ok_or(PinError::UnsupportedSignal { pin, signal })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signals_are_found_in_their_alternate_function() {
+        assert_eq!(find(14, "TXD0"), Ok(Function::Alt0));
+        assert_eq!(find(15, "RXD1"), Ok(Function::Alt5));
+        assert_eq!(find(10, "SPI0_MOSI"), Ok(Function::Alt0));
+        assert_eq!(find(18, "PWM0_0"), Ok(Function::Alt5));
+        assert_eq!(find(14, "SDA1"), Err(PinError::UnsupportedSignal { pin: 14, signal: "SDA1" }));
+        assert_eq!(find(60, "TXD0"), Err(PinError::InvalidPin(60)));
+        assert_eq!(signal(2, Function::Alt0), Some("SDA1"));
+        assert_eq!(signal(16, Function::Alt0), None);
+        assert_eq!(signal(2, Function::Output), None);
+    }
+}
@@ -0,0 +1,199 @@
+//! GPIO pin registry
+//!
+//! Keeps track of which driver owns which GPIO pin and which function it selected, so two drivers
+//! cannot reconfigure the same pin behind each other's back (e.g. `uart0` and `mini_uart` both
+//! want GPIO14/15). Drivers claim their pins before touching GPFSEL; a conflicting claim returns
+//! [`PinError::AlreadyClaimed`] naming the current owner.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::gpio::Function;
+//! use crate::drivers::pins;
+//!
+//! pins::claim(14, "uart0", Function::Alt0)?;
+//! pins::claim(14, "mini_uart", Function::Alt5); // Err(AlreadyClaimed { pin: 14, owner: "uart0" })
+//! pins::report(); // Print the pin table to the console
+//! ```
+
+use core::fmt;
+
+use super::gpio::Function;
+
+/// Number of GPIO pins (0-53).
+pub const PIN_COUNT: usize = 54;
+
+/// Who owns a pin and what it is used for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Claim {
+    /// Name of the owning driver (e.g. "uart0").
+    pub owner: &'static str,
+    /// Function the owner selected for the pin.
+    pub function: Function,
+}
+
+/// Errors returned by the pin registry.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PinError {
+    /// The pin number does not exist.
+    InvalidPin(u8),
+    /// The pin is already owned by another driver.
+    AlreadyClaimed { pin: u8, owner: &'static str },
+    /// The pin cannot carry the requested signal in any alternate function (see `drivers::pinmux`).
+    UnsupportedSignal { pin: u8, signal: &'static str },
+    /// The pin appears more than once in a request for several pins.
+    DuplicatePin(u8),
+}
+
+impl fmt::Display for PinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PinError::InvalidPin(pin) => write!(f, "GPIO{} does not exist", pin),
+            PinError::AlreadyClaimed { pin, owner } => write!(f, "GPIO{} is already owned by {}", pin, owner),
+            PinError::UnsupportedSignal { pin, signal } => write!(f, "GPIO{} cannot carry {}", pin, signal),
+            PinError::DuplicatePin(pin) => write!(f, "GPIO{} is requested more than once", pin),
+        }
+    }
+}
+
+/// Claim `pin` for `owner` with the given function.
+///
+/// Claiming a pin again with the same owner is allowed (e.g. to change its function). Handles that
+/// release the pin when dropped use [`claim_new`] instead.
+pub fn claim(pin: u8, owner: &'static str, function: Function) -> Result<(), PinError> {
+    with_claims(|claims| {
+        let slot = claims.get_mut(pin as usize).ok_or(PinError::InvalidPin(pin))?;
+        match slot {
+            Some(claim) if claim.owner != owner => Err(PinError::AlreadyClaimed { pin, owner: claim.owner }),
+            _ => {
+                *slot = Some(Claim { owner, function });
+                Ok(())
+            }
+        }
+    })
+}
+
+/// Claim `pin` for `owner`, failing if it is owned already, even by `owner` itself.
+///
+/// For handles that release their pins when dropped (`GpioPin`, `GpioPort`): two handles of the
+/// same owner on one pin would fight each other, and the first one dropped would free the pin
+/// under the other.
+pub fn claim_new(pin: u8, owner: &'static str, function: Function) -> Result<(), PinError> {
+    claim_all_new(&[(pin, function)], owner)
+}
+
+/// Claim several pins at once: either all of them are claimed, or none is.
+pub fn claim_all(pins: &[(u8, Function)], owner: &'static str) -> Result<(), PinError> {
+    check_all(pins, |claim| claim.owner != owner)?;
+    for &(pin, function) in pins {
+        claim(pin, owner, function)?;
+    }
+    Ok(())
+}
+
+/// Claim several pins at once with [`claim_new`]: either all of them are claimed, or none is.
+pub fn claim_all_new(pins: &[(u8, Function)], owner: &'static str) -> Result<(), PinError> {
+    check_all(pins, |_| true)?;
+    for &(pin, function) in pins {
+        claim(pin, owner, function)?;
+    }
+    Ok(())
+}
+
+/// Check that `pins` exist and that none has a claim for which `conflicts` is true.
+fn check_all(pins: &[(u8, Function)], conflicts: impl Fn(&Claim) -> bool) -> Result<(), PinError> {
+    for &(pin, _) in pins {
+        if let Some(claim) = owner_of(pin)? {
+            if conflicts(&claim) {
+                return Err(PinError::AlreadyClaimed { pin, owner: claim.owner });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Release `pin` if it is owned by `owner`.
+pub fn release(pin: u8, owner: &'static str) {
+    with_claims(|claims| {
+        if let Some(slot) = claims.get_mut(pin as usize) {
+            if matches!(slot, Some(claim) if claim.owner == owner) {
+                *slot = None;
+            }
+        }
+    })
+}
+
+/// Current claim on `pin` (`None` if the pin is free).
+pub fn owner_of(pin: u8) -> Result<Option<Claim>, PinError> {
+    with_claims(|claims| claims.get(pin as usize).copied().ok_or(PinError::InvalidPin(pin)))
+}
+
+/// Print which driver owns which pin (typically once at boot, after the console is up).
+pub fn report() {
+    crate::println!("GPIO pin claims:");
+    for pin in 0..PIN_COUNT as u8 {
+        if let Ok(Some(claim)) = owner_of(pin) {
+            let signal = super::pinmux::signal(pin, claim.function).unwrap_or("");
+            crate::println!("  GPIO{:<2}  {:<6}  {:<10}  {}", pin, claim.function.name(), signal, claim.owner);
+        }
+    }
+}
+
+/// Run `f` with the claim table.
+#[cfg(not(test))]
+fn with_claims<T>(f: impl FnOnce(&mut [Option<Claim>; PIN_COUNT]) -> T) -> T {
+    static mut CLAIMS: [Option<Claim>; PIN_COUNT] = [None; PIN_COUNT];
+    // Safety: single core, and pins are only claimed from thread context (never from interrupts).
+    f(unsafe { &mut *core::ptr::addr_of_mut!(CLAIMS) })
+}
+
+/// Run `f` with the claim table. Host tests get one table per test thread, like the simulated registers.
+#[cfg(test)]
+fn with_claims<T>(f: impl FnOnce(&mut [Option<Claim>; PIN_COUNT]) -> T) -> T {
+    extern crate std;
+    std::thread_local! {
+        static CLAIMS: core::cell::RefCell<[Option<Claim>; PIN_COUNT]> =
+            const { core::cell::RefCell::new([None; PIN_COUNT]) };
+    }
+    CLAIMS.with(|claims| f(&mut claims.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicting_claims_are_rejected_until_released() {
+        assert_eq!(claim(14, "uart0", Function::Alt0), Ok(()));
+        assert_eq!(claim(14, "uart0", Function::Alt0), Ok(()));
+        assert_eq!(
+            claim(14, "mini_uart", Function::Alt5),
+            Err(PinError::AlreadyClaimed { pin: 14, owner: "uart0" })
+        );
+        release(14, "mini_uart"); // Not the owner: no effect
+        assert_eq!(owner_of(14), Ok(Some(Claim { owner: "uart0", function: Function::Alt0 })));
+        release(14, "uart0");
+        assert_eq!(claim(14, "mini_uart", Function::Alt5), Ok(()));
+        assert_eq!(claim(54, "mini_uart", Function::Alt5), Err(PinError::InvalidPin(54)));
+    }
+
+    #[test]
+    fn new_claims_are_exclusive_even_for_the_same_owner() {
+        assert_eq!(claim_new(42, "act_led", Function::Output), Ok(()));
+        assert_eq!(
+            claim_new(42, "act_led", Function::Output),
+            Err(PinError::AlreadyClaimed { pin: 42, owner: "act_led" })
+        );
+        assert_eq!(claim(42, "act_led", Function::Input), Ok(())); // Recording a new function
+        let result = claim_all_new(&[(41, Function::Input), (42, Function::Input)], "act_led");
+        assert_eq!(result, Err(PinError::AlreadyClaimed { pin: 42, owner: "act_led" }));
+        assert_eq!(owner_of(41), Ok(None));
+    }
+
+    #[test]
+    fn claim_all_is_all_or_nothing() {
+        claim(15, "uart0", Function::Alt0).unwrap();
+        let result = claim_all(&[(14, Function::Alt5), (15, Function::Alt5)], "mini_uart");
+        assert_eq!(result, Err(PinError::AlreadyClaimed { pin: 15, owner: "uart0" }));
+        assert_eq!(owner_of(14), Ok(None));
+    }
+}
@@ -0,0 +1,140 @@
+//! ARM generic timer
+//!
+//! Every core has a 64-bit counter (CNTPCT_EL0) running at a fixed frequency (CNTFRQ_EL0: 54 MHz
+//! on the Pi 4, 19.2 MHz on the Pi 3), independent of the CPU clock and frequency scaling. It only
+//! counts up, so it is used for busy-wait delays and for the deadlines of timeouts.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::timer::{self, Deadline};
+//!
+//! timer::delay_us(500);
+//! let (frequency, start) = timer::counter();
+//!
+//! let deadline = Deadline::after_us(10_000);
+//! while !uart0::is_data_ready() {
+//!     if deadline.has_passed() {
+//!         break; // Gave up after 10 ms
+//!     }
+//! }
+//! ```
+
+/// Busy-wait for at least `us` microseconds.
+pub fn delay_us(us: u32) {
+    let deadline = Deadline::after_us(us);
+    while !deadline.has_passed() {
+        core::hint::spin_loop();
+    }
+}
+
+/// A point in time, `us` microseconds after it was created.
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    start: u64,
+    ticks: u64,
+}
+
+impl Deadline {
+    pub fn after_us(us: u32) -> Self {
+        let (frequency, start) = counter();
+        // Round up: never expire earlier than asked
+        Deadline { start, ticks: (us as u64 * frequency).div_ceil(1_000_000) }
+    }
+
+    pub fn has_passed(&self) -> bool {
+        counter().1.wrapping_sub(self.start) >= self.ticks
+    }
+}
+
+/// Frequency and current value of the generic timer's physical counter.
+#[cfg(all(target_arch = "aarch64", not(test)))]
+pub fn counter() -> (u64, u64) {
+    let (frequency, count): (u64, u64);
+    unsafe {
+        core::arch::asm!(
+            "mrs {}, cntfrq_el0",
+            "isb",
+            "mrs {}, cntpct_el0",
+            out(reg) frequency,
+            out(reg) count,
+            options(nomem, nostack)
+        )
+    };
+    (frequency, count)
+}
+
+/// Host builds have no generic timer: delays return immediately, and deadlines have passed as soon
+/// as they are created.
+#[cfg(all(not(target_arch = "aarch64"), not(test)))]
+pub fn counter() -> (u64, u64) {
+    (0, 0)
+}
+
+/// Frequency and current value of the simulated counter (see [`sim`]).
+#[cfg(test)]
+pub fn counter() -> (u64, u64) {
+    sim::read()
+}
+
+/// Simulated generic timer for host unit tests.
+///
+/// Each test thread has its own counter. It starts at frequency 0, where every delay returns
+/// immediately and every deadline has passed as soon as it is created. A test that needs time to
+/// pass sets a frequency, then moves the counter with [`advance`](sim::advance), or lets every
+/// read advance it by a `step` (so code busy-waiting on the counter sees time pass).
+#[cfg(test)]
+pub mod sim {
+    extern crate std;
+
+    use std::cell::Cell;
+
+    std::thread_local! {
+        /// Frequency, count and step per read.
+        static COUNTER: Cell<(u64, u64, u64)> = const { Cell::new((0, 0, 0)) };
+    }
+
+    /// Run the counter at `frequency` Hz, advancing by `step` ticks on every read.
+    pub fn set(frequency: u64, step: u64) {
+        COUNTER.with(|counter| counter.set((frequency, counter.get().1, step)));
+    }
+
+    /// Move the counter forward by `ticks`.
+    pub fn advance(ticks: u64) {
+        COUNTER.with(|counter| {
+            let (frequency, count, step) = counter.get();
+            counter.set((frequency, count.wrapping_add(ticks), step));
+        });
+    }
+
+    pub(super) fn read() -> (u64, u64) {
+        COUNTER.with(|counter| {
+            let (frequency, count, step) = counter.get();
+            counter.set((frequency, count.wrapping_add(step), step));
+            (frequency, count)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadlines_pass_exactly_when_their_time_is_up() {
+        sim::set(54_000_000, 0); // Pi 4
+        sim::advance(u64::MAX - 100); // Close to the wrap-around
+        let deadline = Deadline::after_us(10);
+        sim::advance(539);
+        assert!(!deadline.has_passed()); // 9.98 us
+        sim::advance(1);
+        assert!(deadline.has_passed()); // 10 us, across the wrap-around
+        assert!(Deadline::after_us(0).has_passed());
+
+        sim::set(19_200_000, 0); // Pi 3: 1 us is 19.2 ticks, rounded up
+        let deadline = Deadline::after_us(1);
+        sim::advance(19);
+        assert!(!deadline.has_passed());
+        sim::advance(1);
+        assert!(deadline.has_passed());
+    }
+}
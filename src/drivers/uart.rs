@@ -13,9 +13,15 @@
 //!
 //! # Features
 //! - Supports both Mini UART and PL011 UART (uart0)
+//! - All PL011 instances, including UART2-5 of the BCM2711 ([`Pl011`])
+//! - A common [`SerialPort`] trait for all UARTs (e.g. to select the console, see `console`)
 //! - Simple API for initialization, sending, and receiving data
-//! - Blocking read/write operations
-//! - Line input with basic editing (backspace)
+//! - Configurable baud rate and frame format ([`UartConfig`]), divisors computed from the board clocks
+//! - Blocking read/write operations, and reads with a timeout (`read_byte_timeout`,
+//!   `read_exact_timeout`, `read_line_timeout`; [`ReadError`]) that cannot hang on a silent line
+//! - Receive errors reported per byte and counted per kind ([`UartError`], `try_read_byte`)
+//! - Interrupt-driven mode for UART0 with RX/TX ring buffers (`uart0::enable_interrupts`)
+//! - Line input with shell-like editing, history and tab completion, on any serial port ([`line_editor`])
 //!
 //! # Example
 //! ```rust
@@ -23,7 +29,8 @@
 //!
 //! fn main() {
 //!     // Initialize the UART (select which one in the driver)
-//!     uart::uart0::init();
+//!     let peripherals = Peripherals::take().unwrap();
+//!     uart::uart0::init(peripherals.uart0, &UartConfig::default()).unwrap();
 //!
 //!     // Write a string
 //!     uart::uart0::write_string("Hello, UART!\r\n");
@@ -35,13 +42,32 @@
 //!
 //!     // Read a line into a buffer
 //!     let mut buf = [0u8; 128];
-//!     if let Some(len) = uart::uart0::read_line(&mut buf) {
+//!     if let Ok(line) = uart::uart0::read_line(&mut buf) {
 //!         // Do something with the input
 //!     }
+//!
+//!     // Wait at most 100 ms for a 4-byte reply
+//!     let mut reply = [0u8; 4];
+//!     match uart::uart0::read_exact_timeout(&mut reply, 100_000) {
+//!         Ok(()) => {}                                   // Got it
+//!         Err(uart::ReadError::Timeout) => {}            // Retry or give up
+//!         Err(uart::ReadError::Uart(error)) => {}        // Damaged byte
+//!     }
 //! }
 //! ```
 //!
 
 
+pub mod config;
+pub mod error;
+pub mod line_editor;
 pub mod mini_uart;
+pub mod pl011;
+pub mod ring_buffer;
+pub mod serial_port;
 pub mod uart0;
+
+pub use config::{ConfigError, DataBits, FlowControl, InitError, Parity, StopBits, UartConfig};
+pub use error::{ErrorCounts, ReadError, UartError};
+pub use pl011::Pl011;
+pub use serial_port::{MiniUartSerial, SerialPort, Uart0Serial};
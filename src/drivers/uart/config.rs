@@ -0,0 +1,201 @@
+//! UART line settings
+//!
+//! [`UartConfig`] describes the baud rate, frame format and flow control of a UART. The drivers
+//! compute their clock divisors from the real UART clock of the board and reject a baud rate they cannot
+//! generate precisely enough ([`ConfigError::BaudRate`], with the error they would achieve).
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::uart::{uart0, FlowControl, Parity, UartConfig};
+//!
+//! uart0::init(peripherals.uart0, &UartConfig::default())?; // 115200 8N1
+//! let modbus = UartConfig { parity: Parity::Even, ..UartConfig::new(19200) };
+//! uart0::configure(&modbus)?;                                  // Reapply at runtime
+//! let modem = UartConfig { flow_control: FlowControl::RtsCts, ..UartConfig::new(921600) };
+//! uart0::configure(&modem)?;                                   // Also claims GPIO16/17 (CTS0/RTS0)
+//! if let Err(error) = uart0::configure(&UartConfig::new(4_000_000)) {
+//!     println!("{}", error); // "4000000 baud not attainable: 3000000 baud is 25.00% off"
+//! }
+//! ```
+
+use core::fmt;
+
+use crate::drivers::pins::PinError;
+
+/// Largest accepted baud rate error, in hundredths of a percent (2%). Both ends of a link may be
+/// off in opposite directions, and a frame of 10-12 bits tolerates about 4-5% in total.
+pub const MAX_BAUD_ERROR: u32 = 200;
+
+/// Number of data bits per character.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    Eight,
+}
+
+/// Parity bit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// Number of stop bits.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// Handshake lines.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FlowControl {
+    /// TX and RX only.
+    None,
+    /// Hardware RTS/CTS handshake: the UART only sends while CTS is asserted, and deasserts RTS
+    /// while its RX FIFO is (nearly) full.
+    RtsCts,
+    /// The RTS pin is driven by software (`set_rts`), CTS is not used. E.g. to switch the direction
+    /// of an RS-485 transceiver.
+    ManualRts,
+}
+
+/// Baud rate, frame format and flow control of a UART.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UartConfig {
+    pub baud: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+}
+
+impl UartConfig {
+    /// `baud` with 8 data bits, no parity and one stop bit (8N1), without flow control.
+    pub const fn new(baud: u32) -> Self {
+        UartConfig {
+            baud,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+
+    /// Time one frame (start bit, data bits, parity bit, stop bits) takes on the line, rounded up.
+    pub fn frame_time_us(&self) -> u32 {
+        let data_bits = match self.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity_bits = if self.parity == Parity::None { 0 } else { 1 };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        let bits: u64 = 1 + data_bits + parity_bits + stop_bits;
+        (bits * 1_000_000).div_ceil(self.baud.max(1) as u64) as u32
+    }
+}
+
+impl Default for UartConfig {
+    /// 115200 8N1, what the console and most USB serial adapters expect.
+    fn default() -> Self {
+        UartConfig::new(115200)
+    }
+}
+
+/// Errors returned when applying a [`UartConfig`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConfigError {
+    /// The baud rate cannot be generated from the UART clock within [`MAX_BAUD_ERROR`].
+    /// `achieved` is the closest rate the UART can generate.
+    BaudRate { requested: u32, achieved: u32 },
+    /// The UART does not support this frame format (e.g. parity on the Mini UART).
+    Unsupported(&'static str),
+    /// A pin needed by the configuration (e.g. RTS/CTS) is owned by another driver.
+    Pin(PinError),
+}
+
+impl ConfigError {
+    /// Build the error for `requested` if `achieved` is too far off.
+    pub fn check_baud(requested: u32, achieved: u32) -> Result<(), ConfigError> {
+        if baud_error(requested, achieved) > MAX_BAUD_ERROR {
+            return Err(ConfigError::BaudRate { requested, achieved });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::BaudRate { requested, achieved } => {
+                let error = baud_error(requested, achieved);
+                write!(
+                    f,
+                    "{} baud not attainable: {} baud is {}.{:02}% off",
+                    requested,
+                    achieved,
+                    error / 100,
+                    error % 100
+                )
+            }
+            ConfigError::Unsupported(what) => write!(f, "{} not supported by this UART", what),
+            ConfigError::Pin(error) => error.fmt(f),
+        }
+    }
+}
+
+/// Error of `achieved` relative to `requested`, in hundredths of a percent.
+pub fn baud_error(requested: u32, achieved: u32) -> u32 {
+    if requested == 0 {
+        return u32::MAX;
+    }
+    let difference = (requested as u64).abs_diff(achieved as u64);
+    (difference * 10_000 / requested as u64).min(u32::MAX as u64) as u32
+}
+
+/// Errors returned by the UART `init` functions.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InitError {
+    /// Another driver owns one of the pins.
+    Pin(PinError),
+    /// The configuration cannot be applied.
+    Config(ConfigError),
+    /// The peripheral does not exist on this board (e.g. UART2-5 on the BCM2837).
+    Unavailable(&'static str),
+}
+
+impl From<PinError> for ConfigError {
+    fn from(error: PinError) -> Self {
+        ConfigError::Pin(error)
+    }
+}
+
+impl From<PinError> for InitError {
+    fn from(error: PinError) -> Self {
+        InitError::Pin(error)
+    }
+}
+
+impl From<ConfigError> for InitError {
+    fn from(error: ConfigError) -> Self {
+        InitError::Config(error)
+    }
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InitError::Pin(error) => error.fmt(f),
+            InitError::Config(error) => error.fmt(f),
+            InitError::Unavailable(name) => write!(f, "{} does not exist on this board", name),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! UART receive errors
+//!
+//! A byte can arrive damaged: a wrong stop bit (noise, baud rate mismatch), a wrong parity bit, a
+//! break (RX held low for a whole frame), or the receiver can drop bytes because nobody read its
+//! FIFO in time. `try_read_byte` reports such a byte as a [`UartError`] instead of handing out the
+//! corrupted value (`read_byte` drops it), and every driver counts the errors per kind
+//! ([`ErrorCounts`]), so a noisy cable shows up as growing counters.
+//!
+//! The blocking reads with a deadline (`read_byte_timeout`, `read_exact_timeout`) fail with a
+//! [`ReadError`]: either nothing arrived in time, or a byte arrived damaged.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::uart::{uart0, UartError};
+//!
+//! match uart0::try_read_byte() {
+//!     Some(Ok(byte)) => uart0::write_byte(byte),
+//!     Some(Err(UartError::Break)) => println!("break"),
+//!     Some(Err(error)) => println!("dropped a byte: {}", error),
+//!     None => {} // Nothing received
+//! }
+//! println!("{}", uart0::error_counts()); // "framing 0, parity 0, break 1, overrun 0"
+//! ```
+
+use core::fmt;
+
+/// Why a received byte was rejected.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UartError {
+    /// The byte did not end with a valid stop bit (noise, or a baud rate mismatch).
+    Framing,
+    /// The parity bit of the byte did not match.
+    Parity,
+    /// RX was held low for longer than a whole frame.
+    Break,
+    /// Bytes were lost before this point because the receiver (or the RX buffer) was full.
+    Overrun,
+}
+
+impl fmt::Display for UartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UartError::Framing => write!(f, "framing error"),
+            UartError::Parity => write!(f, "parity error"),
+            UartError::Break => write!(f, "break"),
+            UartError::Overrun => write!(f, "overrun, bytes lost"),
+        }
+    }
+}
+
+/// Why a blocking read with a timeout failed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReadError {
+    /// Not enough bytes arrived before the deadline.
+    Timeout,
+    /// A byte was received with an error.
+    Uart(UartError),
+}
+
+impl From<UartError> for ReadError {
+    fn from(error: UartError) -> Self {
+        ReadError::Uart(error)
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Timeout => write!(f, "timed out"),
+            ReadError::Uart(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Number of receive errors of each kind since the UART was initialized (or the counts reset).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ErrorCounts {
+    pub framing: u32,
+    pub parity: u32,
+    pub breaks: u32,
+    pub overruns: u32,
+}
+
+impl ErrorCounts {
+    pub const fn new() -> Self {
+        ErrorCounts { framing: 0, parity: 0, breaks: 0, overruns: 0 }
+    }
+
+    /// Count one error of kind `error`.
+    pub fn record(&mut self, error: UartError) {
+        let count = match error {
+            UartError::Framing => &mut self.framing,
+            UartError::Parity => &mut self.parity,
+            UartError::Break => &mut self.breaks,
+            UartError::Overrun => &mut self.overruns,
+        };
+        *count = count.saturating_add(1);
+    }
+
+    /// Errors of all kinds.
+    pub fn total(&self) -> u32 {
+        self.framing.saturating_add(self.parity).saturating_add(self.breaks).saturating_add(self.overruns)
+    }
+}
+
+impl fmt::Display for ErrorCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "framing {}, parity {}, break {}, overrun {}", self.framing, self.parity, self.breaks, self.overruns)
+    }
+}
@@ -0,0 +1,622 @@
+//! Line editor for console input
+//!
+//! Reads a line from any [`SerialPort`] with the editing keys of a shell, understanding the
+//! ANSI/VT100 escape sequences terminals send for the cursor keys:
+//!
+//! | Key                      | Action                                   |
+//! |--------------------------|------------------------------------------|
+//! | Left / Right, Ctrl-B / F | Move the cursor                          |
+//! | Home / End, Ctrl-A / E   | Start / end of the line                  |
+//! | Up / Down, Ctrl-P / N    | Previous / next line of the history      |
+//! | Backspace / Delete       | Delete before / at the cursor            |
+//! | Ctrl-K / Ctrl-U          | Delete to the end / start of the line    |
+//! | Ctrl-W                   | Delete the word before the cursor        |
+//! | Tab                      | Complete the word (see [`Completer`])    |
+//! | Ctrl-C                   | Abandon the line ([`LineError::Interrupted`]) |
+//! | Ctrl-D                   | Delete at the cursor, or end of input on an empty line |
+//!
+//! Only printable ASCII is inserted, so the line is always a valid `&str`. The line is edited in
+//! the caller's buffer; a [`LineEditor`] adds the history ring (`HISTORY` lines of up to `LEN`
+//! bytes) and tab completion. Bytes received with an error are skipped (see `SerialPort::error_counts`).
+//!
+//! `read_line` waits for Enter forever; `read_line_timeout` gives up with [`LineError::Timeout`]
+//! when the whole line has not arrived in time (e.g. the terminal was disconnected).
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::uart::line_editor::{LineEditor, LineError};
+//! use crate::drivers::uart::Uart0Serial;
+//!
+//! fn commands(line: &str, complete: &mut dyn FnMut(&'static str)) {
+//!     if !line.contains(' ') {
+//!         ["help", "led", "reboot"].into_iter().for_each(complete); // Complete the command name
+//!     }
+//! }
+//!
+//! let mut editor = LineEditor::<8, 80>::new();
+//! editor.set_completer(Some(commands));
+//! let mut buffer = [0u8; 80];
+//! loop {
+//!     match editor.read_line(&Uart0Serial, "> ", &mut buffer) {
+//!         Ok(line) => run(line),
+//!         Err(LineError::Interrupted) => continue, // Ctrl-C
+//!         Err(LineError::EndOfInput) => break,     // Ctrl-D
+//!     }
+//! }
+//! ```
+
+use core::fmt::{self, Write};
+
+use super::serial_port::SerialPort;
+use crate::drivers::timer::Deadline;
+
+/// Tab completion callback: call `complete` with the candidates for the last word of `line` (the
+/// text before the cursor). Candidates not starting with that word are ignored.
+pub type Completer = fn(line: &str, complete: &mut dyn FnMut(&'static str));
+
+/// Why no line was read.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineError {
+    /// Ctrl-C: the line was abandoned.
+    Interrupted,
+    /// Ctrl-D on an empty line.
+    EndOfInput,
+    /// Enter was not pressed before the deadline.
+    Timeout,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LineError::Interrupted => write!(f, "interrupted"),
+            LineError::EndOfInput => write!(f, "end of input"),
+            LineError::Timeout => write!(f, "timed out"),
+        }
+    }
+}
+
+/// Read a line with editing but without history or completion.
+pub fn read_line<'a>(port: &dyn SerialPort, buffer: &'a mut [u8]) -> Result<&'a str, LineError> {
+    LineEditor::<0, 0>::new().read_line(port, "", buffer)
+}
+
+/// Read a line with editing, giving up if Enter was not pressed within `timeout_us` microseconds.
+pub fn read_line_timeout<'a>(
+    port: &dyn SerialPort,
+    buffer: &'a mut [u8],
+    timeout_us: u32,
+) -> Result<&'a str, LineError> {
+    LineEditor::<0, 0>::new().read_line_timeout(port, "", buffer, timeout_us)
+}
+
+/// Line editing with a history of `HISTORY` lines of up to `LEN` bytes.
+pub struct LineEditor<const HISTORY: usize, const LEN: usize> {
+    history: [[u8; LEN]; HISTORY],
+    history_lengths: [usize; HISTORY],
+    /// Number of lines in the history.
+    history_count: usize,
+    /// Slot of the newest line.
+    newest: usize,
+    completer: Option<Completer>,
+    /// The last line ended with CR: ignore a following LF (terminals sending CR LF).
+    skip_lf: bool,
+}
+
+impl<const HISTORY: usize, const LEN: usize> LineEditor<HISTORY, LEN> {
+    pub const fn new() -> Self {
+        LineEditor {
+            history: [[0; LEN]; HISTORY],
+            history_lengths: [0; HISTORY],
+            history_count: 0,
+            newest: 0,
+            completer: None,
+            skip_lf: false,
+        }
+    }
+
+    pub fn set_completer(&mut self, completer: Option<Completer>) {
+        self.completer = completer;
+    }
+
+    /// Lines in the history, newest first.
+    pub fn history(&self) -> impl Iterator<Item = &str> + '_ {
+        (0..self.history_count).map(move |age| self.history_line(age))
+    }
+
+    /// Print `prompt`, then read and edit a line in `buffer` until Enter is pressed. Blocks until
+    /// then. Non-empty lines are added to the history.
+    pub fn read_line<'a>(
+        &mut self,
+        port: &dyn SerialPort,
+        prompt: &str,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a str, LineError> {
+        self.edit(port, prompt, buffer, None)
+    }
+
+    /// Like `read_line`, but give up if Enter was not pressed within `timeout_us` microseconds.
+    /// The partial line is dropped.
+    pub fn read_line_timeout<'a>(
+        &mut self,
+        port: &dyn SerialPort,
+        prompt: &str,
+        buffer: &'a mut [u8],
+        timeout_us: u32,
+    ) -> Result<&'a str, LineError> {
+        self.edit(port, prompt, buffer, Some(Deadline::after_us(timeout_us)))
+    }
+
+    fn edit<'a>(
+        &mut self,
+        port: &dyn SerialPort,
+        prompt: &str,
+        buffer: &'a mut [u8],
+        deadline: Option<Deadline>,
+    ) -> Result<&'a str, LineError> {
+        port.write_string(prompt);
+        let mut line = Line { port, prompt, buffer, len: 0, cursor: 0 };
+        let mut escape = Escape::None;
+        // Position in the history while browsing it (0: the line being typed)
+        let mut browsing = 0;
+        loop {
+            // Checked whatever arrives: a stream of bytes without Enter must time out too
+            if deadline.is_some_and(|deadline| deadline.has_passed()) {
+                port.write_string("\r\n");
+                return Err(LineError::Timeout);
+            }
+            let byte = match port.try_read_byte() {
+                Some(Ok(byte)) => byte,
+                Some(Err(_)) | None => continue, // Nothing received, or a corrupted byte
+            };
+            if core::mem::take(&mut self.skip_lf) && byte == b'\n' {
+                continue;
+            }
+            let key = match escape.feed(byte) {
+                Some(key) => key,
+                None => continue, // Inside an escape sequence
+            };
+            match key {
+                Key::Enter(cr) => {
+                    self.skip_lf = cr;
+                    port.write_string("\r\n");
+                    let len = line.len;
+                    self.remember(&line.buffer[..len]);
+                    // Only printable ASCII was inserted
+                    return Ok(core::str::from_utf8(&line.buffer[..len]).unwrap_or(""));
+                }
+                Key::Char(byte) => line.insert(byte),
+                Key::Left => line.move_to(line.cursor.saturating_sub(1)),
+                Key::Right => line.move_to((line.cursor + 1).min(line.len)),
+                Key::Home => line.move_to(0),
+                Key::End => line.move_to(line.len),
+                Key::Backspace if line.cursor > 0 => {
+                    line.move_to(line.cursor - 1);
+                    line.delete(line.cursor, line.cursor + 1);
+                }
+                Key::Delete => line.delete(line.cursor, (line.cursor + 1).min(line.len)),
+                Key::KillToEnd => line.delete(line.cursor, line.len),
+                Key::KillToStart => {
+                    let end = line.cursor;
+                    line.move_to(0);
+                    line.delete(0, end);
+                }
+                Key::KillWord => {
+                    let end = line.cursor;
+                    let text = &line.buffer[..end];
+                    let word_end = text.iter().rposition(|&byte| byte != b' ').map_or(0, |last| last + 1);
+                    let start = text[..word_end].iter().rposition(|&byte| byte == b' ').map_or(0, |space| space + 1);
+                    line.move_to(start);
+                    line.delete(start, end);
+                }
+                Key::Up if browsing < self.history_count => {
+                    browsing += 1;
+                    line.replace(self.history_line(browsing - 1).as_bytes());
+                }
+                Key::Down if browsing > 0 => {
+                    browsing -= 1;
+                    match browsing {
+                        0 => line.replace(b""),
+                        age => line.replace(self.history_line(age - 1).as_bytes()),
+                    }
+                }
+                Key::Tab => match self.completer {
+                    Some(completer) => line.complete(completer),
+                    None => line.bell(),
+                },
+                Key::Interrupt => {
+                    port.write_string("^C\r\n");
+                    return Err(LineError::Interrupted);
+                }
+                Key::EndOfInput if line.len == 0 => {
+                    port.write_string("\r\n");
+                    return Err(LineError::EndOfInput);
+                }
+                Key::EndOfInput => line.delete(line.cursor, (line.cursor + 1).min(line.len)),
+                _ => {} // Nothing to move or delete, or a key we do not handle
+            }
+        }
+    }
+
+    /// Add `line` to the history, unless it is empty or repeats the newest line.
+    fn remember(&mut self, line: &[u8]) {
+        if HISTORY == 0 || line.is_empty() || (self.history_count > 0 && self.history_line(0).as_bytes() == line) {
+            return;
+        }
+        let len = line.len().min(LEN);
+        self.newest = (self.newest + 1) % HISTORY;
+        self.history[self.newest][..len].copy_from_slice(&line[..len]);
+        self.history_lengths[self.newest] = len;
+        self.history_count = (self.history_count + 1).min(HISTORY);
+    }
+
+    /// History line `age` lines back (0: newest).
+    fn history_line(&self, age: usize) -> &str {
+        let slot = (self.newest + HISTORY - age) % HISTORY;
+        core::str::from_utf8(&self.history[slot][..self.history_lengths[slot]]).unwrap_or("")
+    }
+}
+
+impl<const HISTORY: usize, const LEN: usize> Default for LineEditor<HISTORY, LEN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keys, after decoding escape sequences and control characters.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Key {
+    Char(u8),
+    /// CR (`true`) or LF.
+    Enter(bool),
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Backspace,
+    Delete,
+    KillToEnd,
+    KillToStart,
+    KillWord,
+    Tab,
+    Interrupt,
+    EndOfInput,
+    Ignored,
+}
+
+/// Escape sequence decoder: ESC [ <params> <final> (CSI) and ESC O <final> (SS3).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Escape {
+    None,
+    /// ESC received.
+    Start,
+    /// ESC O received.
+    Ss3,
+    /// ESC [ received, with the numeric parameter so far.
+    Csi(u8),
+}
+
+impl Escape {
+    /// Feed one received byte. Returns the key once a key is complete.
+    fn feed(&mut self, byte: u8) -> Option<Key> {
+        let key = match (*self, byte) {
+            (Escape::None, 0x1B) => {
+                *self = Escape::Start;
+                return None;
+            }
+            (Escape::None, byte) => control_key(byte),
+            (Escape::Start, b'[') => {
+                *self = Escape::Csi(0);
+                return None;
+            }
+            (Escape::Start, b'O') => {
+                *self = Escape::Ss3;
+                return None;
+            }
+            (Escape::Start, _) => Key::Ignored, // Alt-<key>: not handled
+            (Escape::Csi(parameter), b'0'..=b'9') => {
+                *self = Escape::Csi(parameter.saturating_mul(10).saturating_add(byte - b'0'));
+                return None;
+            }
+            (Escape::Csi(_), b';') => return None, // Modifier parameters: ignored
+            (Escape::Csi(parameter), b'~') => match parameter {
+                1 | 7 => Key::Home,
+                3 => Key::Delete,
+                4 | 8 => Key::End,
+                _ => Key::Ignored,
+            },
+            (Escape::Csi(_) | Escape::Ss3, byte) => match byte {
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                b'H' => Key::Home,
+                b'F' => Key::End,
+                _ => Key::Ignored,
+            },
+        };
+        *self = Escape::None;
+        Some(key)
+    }
+}
+
+/// Key of a byte outside escape sequences.
+fn control_key(byte: u8) -> Key {
+    match byte {
+        b' '..=b'~' => Key::Char(byte),
+        b'\r' => Key::Enter(true),
+        b'\n' => Key::Enter(false),
+        0x08 | 0x7F => Key::Backspace,
+        b'\t' => Key::Tab,
+        0x01 => Key::Home,        // Ctrl-A
+        0x02 => Key::Left,        // Ctrl-B
+        0x03 => Key::Interrupt,   // Ctrl-C
+        0x04 => Key::EndOfInput,  // Ctrl-D
+        0x05 => Key::End,         // Ctrl-E
+        0x06 => Key::Right,       // Ctrl-F
+        0x0B => Key::KillToEnd,   // Ctrl-K
+        0x0E => Key::Down,        // Ctrl-N
+        0x10 => Key::Up,          // Ctrl-P
+        0x15 => Key::KillToStart, // Ctrl-U
+        0x17 => Key::KillWord,    // Ctrl-W
+        _ => Key::Ignored,
+    }
+}
+
+/// The line being edited and its echo on the terminal.
+struct Line<'p, 'b> {
+    port: &'p dyn SerialPort,
+    prompt: &'p str,
+    buffer: &'b mut [u8],
+    len: usize,
+    cursor: usize,
+}
+
+impl Line<'_, '_> {
+    /// Insert `byte` at the cursor (rings the bell if the buffer is full).
+    fn insert(&mut self, byte: u8) {
+        if self.len == self.buffer.len() {
+            return self.bell();
+        }
+        self.buffer.copy_within(self.cursor..self.len, self.cursor + 1);
+        self.buffer[self.cursor] = byte;
+        self.len += 1;
+        self.cursor += 1;
+        if self.cursor == self.len {
+            self.port.write_byte(byte); // Typing at the end: just echo
+        } else {
+            self.redraw();
+        }
+    }
+
+    /// Delete `start..end` (the cursor stays at or before `start`).
+    fn delete(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        self.buffer.copy_within(end..self.len, start);
+        self.len -= end - start;
+        if self.cursor == self.len && end - start == 1 && start == self.len {
+            self.port.write_string(" \x08"); // Deleted the last character: just blank it
+        } else {
+            self.redraw();
+        }
+    }
+
+    /// Move the cursor to `position`.
+    fn move_to(&mut self, position: usize) {
+        if position < self.cursor {
+            self.cursor_left(self.cursor - position);
+        } else if position > self.cursor {
+            // Rewrite the characters we move over instead of counting columns
+            for index in self.cursor..position {
+                self.port.write_byte(self.buffer[index]);
+            }
+        }
+        self.cursor = position;
+    }
+
+    /// Replace the whole line with `text` (cut to the buffer size), cursor at the end.
+    fn replace(&mut self, text: &[u8]) {
+        self.len = text.len().min(self.buffer.len());
+        self.buffer[..self.len].copy_from_slice(&text[..self.len]);
+        self.cursor = self.len;
+        self.redraw();
+    }
+
+    /// Complete the word before the cursor: insert the longest prefix common to all candidates,
+    /// or list them if that adds nothing.
+    fn complete(&mut self, completer: Completer) {
+        let text = core::str::from_utf8(&self.buffer[..self.cursor]).unwrap_or("");
+        let word = &text[text.rfind(' ').map_or(0, |space| space + 1)..];
+        let mut count = 0;
+        let mut first: &'static str = "";
+        let mut common = 0;
+        completer(text, &mut |candidate| {
+            if !candidate.starts_with(word) {
+                return;
+            }
+            if count == 0 {
+                first = candidate;
+                common = candidate.len();
+            } else {
+                common = first.bytes().zip(candidate.bytes()).take(common).take_while(|(a, b)| a == b).count();
+            }
+            count += 1;
+        });
+        let word_len = word.len();
+        match count {
+            0 => self.bell(),
+            _ if common > word_len => {
+                first.bytes().take(common).skip(word_len).for_each(|byte| self.insert(byte));
+                if count == 1 {
+                    self.insert(b' ');
+                }
+            }
+            1 => self.insert(b' '),
+            _ => {
+                // Ambiguous: list the candidates below, then show the line again
+                self.port.write_string("\r\n");
+                completer(text, &mut |candidate| {
+                    if candidate.starts_with(word) {
+                        self.port.write_string(candidate);
+                        self.port.write_string("  ");
+                    }
+                });
+                self.port.write_string("\r\n");
+                self.port.write_string(self.prompt);
+                let (len, cursor) = (self.len, self.cursor);
+                self.cursor = len;
+                self.buffer[..len].iter().for_each(|&byte| self.port.write_byte(byte));
+                self.move_to(cursor);
+            }
+        }
+    }
+
+    /// Draw the prompt and the line again, clear the rest of the terminal line, and put the
+    /// cursor back.
+    fn redraw(&mut self) {
+        self.port.write_string("\r");
+        self.port.write_string(self.prompt);
+        for &byte in &self.buffer[..self.len] {
+            self.port.write_byte(byte);
+        }
+        self.port.write_string("\x1b[K"); // Erase to the end of the terminal line
+        self.cursor_left(self.len - self.cursor);
+    }
+
+    fn cursor_left(&self, columns: usize) {
+        if columns > 0 {
+            write!(PortWriter(self.port), "\x1b[{}D", columns).ok();
+        }
+    }
+
+    fn bell(&self) {
+        self.port.write_byte(0x07);
+    }
+}
+
+/// `fmt::Write` on a serial port, for escape sequences with numbers.
+struct PortWriter<'a>(&'a dyn SerialPort);
+
+impl Write for PortWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_string(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::timer;
+    use crate::drivers::uart::{ConfigError, ErrorCounts, UartConfig, UartError};
+    use core::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    /// In a `Terminal` script: one poll that receives nothing.
+    const PAUSE: u8 = 0xFF;
+    /// In a `Terminal` script: one poll that receives a byte with a framing error.
+    const NOISE: u8 = 0xFE;
+
+    /// A terminal typing `input`.
+    struct Terminal {
+        input: RefCell<VecDeque<u8>>,
+        output: RefCell<Vec<u8>>,
+    }
+
+    impl Terminal {
+        fn typing(input: &[u8]) -> Self {
+            Terminal { input: RefCell::new(input.iter().copied().collect()), output: RefCell::new(Vec::new()) }
+        }
+    }
+
+    impl SerialPort for Terminal {
+        fn name(&self) -> &'static str {
+            "terminal"
+        }
+        fn write_byte(&self, byte: u8) {
+            self.output.borrow_mut().push(byte);
+        }
+        fn read_byte(&self) -> Option<u8> {
+            self.input.borrow_mut().pop_front()
+        }
+        fn try_read_byte(&self) -> Option<Result<u8, UartError>> {
+            match self.read_byte()? {
+                PAUSE => None,
+                NOISE => Some(Err(UartError::Framing)),
+                byte => Some(Ok(byte)),
+            }
+        }
+        fn is_data_ready(&self) -> bool {
+            !self.input.borrow().is_empty()
+        }
+        fn flush(&self) {}
+        fn send_break(&self, _duration_us: u32) {}
+        fn configure(&self, _config: &UartConfig) -> Result<(), ConfigError> {
+            Ok(())
+        }
+        fn config(&self) -> UartConfig {
+            UartConfig::default()
+        }
+        fn error_counts(&self) -> ErrorCounts {
+            ErrorCounts::new()
+        }
+    }
+
+    fn commands(_line: &str, complete: &mut dyn FnMut(&'static str)) {
+        ["help", "led", "led_off", "reboot"].into_iter().for_each(complete);
+    }
+
+    #[test]
+    fn lines_are_edited_with_terminal_keys_and_remembered() {
+        let mut editor = LineEditor::<4, 32>::new();
+        editor.set_completer(Some(commands));
+        let mut buffer = [0u8; 32];
+        // Typing, Left + insert, Home + insert, Ctrl-E, Backspace, Delete at the end (nothing)
+        let terminal = Terminal::typing(b"hllo\x1b[D\x1b[D\x1b[Dex\x01X\x05\x7f\x1b[3~\r");
+        assert_eq!(editor.read_line(&terminal, "> ", &mut buffer), Ok("Xhexll"));
+        assert!(terminal.output.borrow().starts_with(b"> hllo"));
+
+        // Ctrl-W, Ctrl-U, a unique completion, Backspace, Ctrl-C
+        let terminal = Terminal::typing(b"led on\x17\x15re\tnow\x7f\x7f\x7f\x03");
+        assert_eq!(editor.read_line(&terminal, "> ", &mut buffer), Err(LineError::Interrupted));
+        assert!(terminal.output.borrow().ends_with(b"reboot now\x1b[1D \x08\x1b[1D \x08\x1b[1D \x08^C\r\n"));
+
+        let terminal = Terminal::typing(b"re\t\rle\t\t_\t\r");
+        assert_eq!(editor.read_line(&terminal, "> ", &mut buffer), Ok("reboot "));
+        assert_eq!(editor.read_line(&terminal, "> ", &mut buffer), Ok("led_off "));
+        assert!(terminal.output.borrow().windows(15).any(|text| text == b"led  led_off  \r")); // Listed
+
+        // History: Up twice, Down once; then the LF of CR LF is skipped, Ctrl-K after Ctrl-A
+        let terminal = Terminal::typing(b"\x1b[A\x1b[A\x1b[B\r\n\x1b[A\x01\x0b\x04");
+        assert_eq!(editor.read_line(&terminal, "> ", &mut buffer), Ok("led_off "));
+        assert_eq!(editor.read_line(&terminal, "> ", &mut buffer), Err(LineError::EndOfInput));
+        assert_eq!(editor.history().collect::<Vec<_>>(), ["led_off ", "reboot ", "Xhexll"]);
+    }
+
+    #[test]
+    fn unfinished_lines_time_out() {
+        // 1 MHz counter advancing 1 us per read: every poll of the editor takes 1 us
+        timer::sim::set(1_000_000, 1);
+        let mut editor = LineEditor::<4, 32>::new();
+        let mut buffer = [0u8; 32];
+        let mut slow = b"o".to_vec();
+        slow.extend([PAUSE; 900]);
+        slow.extend(b"k\r");
+        let terminal = Terminal::typing(&slow);
+        assert_eq!(editor.read_line_timeout(&terminal, "> ", &mut buffer, 1000), Ok("ok")); // In time
+
+        // Silence, a stream of noise and a stream of bytes without Enter all give up at the deadline
+        for script in [[PAUSE; 2000], [NOISE; 2000], [b'x'; 2000]] {
+            let terminal = Terminal::typing(&script);
+            assert_eq!(editor.read_line_timeout(&terminal, "> ", &mut buffer, 1000), Err(LineError::Timeout));
+            let left = terminal.input.borrow().len();
+            assert!((999..=1001).contains(&left), "gave up after {} polls", 2000 - left);
+            assert!(terminal.output.borrow().ends_with(b"\r\n"));
+        }
+        assert_eq!(editor.history().collect::<Vec<_>>(), ["ok"]);
+    }
+}
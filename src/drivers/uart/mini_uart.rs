@@ -2,49 +2,178 @@
 //!
 //! Provides blocking read/write and initialization routines for the Mini UART peripheral.
 //!
+//! The Mini UART baud rate is derived from the VPU core clock, which the firmware may change
+//! (frequency scaling, `core_freq`). `init` and `configure` ask the firmware for the current core
+//! clock (falling back to the board value, or the last known one, when it does not answer).
+//! Code that changes the core clock must call [`sync_core_clock`] afterwards, or
+//! [`set_core_clock`] with the new rate, or the baud rate changes with it. To keep it from changing
+//! behind the kernel's back, fix it in `config.txt` (`enable_uart=1` or `core_freq`).
+//!
+//! The only receive error the Mini UART detects is an overrun (LSR bit 1, cleared by reading LSR),
+//! so every LSR read goes through `line_status` to not lose one. There is no framing, parity or
+//! break detection, but [`send_break`] can send a break.
+//!
 //! # Example
 //! ```rust
 //! use crate::drivers::uart::mini_uart;
 //!
 //! fn main() {
-//!     mini_uart::init();
+//!     let peripherals = Peripherals::take().unwrap();
+//!     mini_uart::init(peripherals.mini_uart, &UartConfig::default()).unwrap(); // Claims GPIO14/15
 //!     mini_uart::write_string("Hello from Mini UART!\r\n");
 //!     if let Some(byte) = mini_uart::read_byte() {
 //!         mini_uart::write_byte(byte);
 //!     }
+//!     mini_uart::configure(&UartConfig::new(57600)).unwrap(); // Change the baud rate
+//!     mini_uart::sync_core_clock().unwrap();                   // Follow a core clock change
 //! }
 //! ```
 
-use crate::hal::registers::uart::MINI_UART_REGS;
-use crate::hal::registers::gpio::GPIO_REGS;
-use crate::hal::registers::utils::*;
-use crate::hal::registers::auxiliary::AUX_REGS;
-
-pub fn init() {
-    unsafe {
-        let gpio_regs = &mut *GPIO_REGS;
-        gpio_regs.gpfsel1 &= !(0b111 << 12); // Clear FSEL14 (TX)
-        gpio_regs.gpfsel1 &= !(0b111 << 15); // Clear FSEL15 (RX)
-        gpio_regs.gpfsel1 |= 0b010 << 12; // Set GPIO14 to ALT5 (Mini UART TX)
-        gpio_regs.gpfsel1 |= 0b010 << 15; // Set GPIO15 to ALT5 (Mini UART RX)
-        let aux_regs = &mut *AUX_REGS;
-        set_bit(&mut aux_regs.aux_enables, 0); // Enable Mini UART peripheral
-        let mini_uart_regs = &mut *MINI_UART_REGS;
-        mini_uart_regs.aux_mu_baud_reg = 270; // Set baud rate to 115200 (assuming 250MHz clock)
-        set_bit(&mut mini_uart_regs.aux_mu_lcr_reg, 0); // 8-bit mode
-        set_bit(&mut mini_uart_regs.aux_mu_cntl_reg, 0); // Enable receiver
-        set_bit(&mut mini_uart_regs.aux_mu_cntl_reg, 1); // Enable transmitter
-        set_bit(&mut mini_uart_regs.aux_mu_iir_reg, 1); // Clear receive FIFO
-        set_bit(&mut mini_uart_regs.aux_mu_iir_reg, 2); // Clear transmit FIFO
+use crate::board;
+use crate::drivers::gpio::{self, Pull};
+use crate::drivers::mailbox::{self, clock};
+use crate::drivers::peripherals::MiniUart;
+use crate::drivers::timer;
+use crate::hal::registers::access::FieldValue;
+use crate::hal::registers::auxiliary::{aux_regs, AUX};
+use crate::hal::registers::uart::{
+    mini_uart_regs, AUX_MU_BAUD, AUX_MU_CNTL, AUX_MU_IIR, AUX_MU_IO, AUX_MU_LCR, AUX_MU_LSR,
+};
+
+use super::config::{ConfigError, DataBits, FlowControl, InitError, Parity, StopBits, UartConfig};
+use super::error::{ErrorCounts, ReadError, UartError};
+use super::line_editor::{self, LineError};
+use super::serial_port::{MiniUartSerial, SerialPort};
+
+/// Name under which the Mini UART claims its pins.
+const OWNER: &str = "mini_uart";
+
+/// Line settings, the core clock the baud rate divisor was computed from, and receive errors.
+struct State {
+    config: UartConfig,
+    core_clock_hz: u32,
+    errors: ErrorCounts,
+    /// An overrun was seen in LSR and not reported by `try_read_byte` yet.
+    overrun_pending: bool,
+}
+
+impl State {
+    const INITIAL: State =
+        State { config: UartConfig::new(115200), core_clock_hz: 0, errors: ErrorCounts::new(), overrun_pending: false };
+}
+
+/// Initialize the Mini UART on GPIO14 (TX) / GPIO15 (RX) with the given line settings.
+/// Fails if another driver owns one of the pins or the Mini UART cannot use the settings.
+pub fn init(_uart: MiniUart, config: &UartConfig) -> Result<(), InitError> {
+    // The board value is the clock at boot (or a compile-time default): prefer the current rate
+    let core_clock_hz = mailbox::clock_rate(clock::CORE).unwrap_or(board::current().core_clock_hz);
+    let baud_reg = divisor(core_clock_hz, config)?; // Before claiming anything
+    // Claim GPIO14 (TX) and GPIO15 (RX) and route TXD1/RXD1 to them (ALT5)
+    gpio::request_signals(&[(14, "TXD1"), (15, "RXD1")], OWNER)?;
+    gpio::set_pull(14, Pull::None); // TX is driven by the UART
+    gpio::set_pull(15, Pull::Up); // Keep RX idle (high) when nothing is connected, no spurious bytes
+    let aux = aux_regs();
+    aux.aux_enables.modify(AUX::MINI_UART::SET); // Enable Mini UART peripheral
+    let regs = mini_uart_regs();
+    regs.aux_mu_baud_reg.write(AUX_MU_BAUD::BAUDRATE.val(baud_reg)); // Set baud rate
+    regs.aux_mu_lcr_reg.write(data_size(config)); // 7 or 8-bit mode
+    regs.aux_mu_cntl_reg.write(AUX_MU_CNTL::RX_ENABLE::SET + AUX_MU_CNTL::TX_ENABLE::SET); // Enable receiver and transmitter
+    regs.aux_mu_iir_reg.write(AUX_MU_IIR::ID_FIFO_CLEAR::ClearAll); // Clear receive and transmit FIFOs
+    with_state(|state| *state = State { config: *config, core_clock_hz, ..State::INITIAL });
+    Ok(())
+}
+
+/// Apply new line settings at runtime, for the current core clock. Pending output is sent with
+/// the old settings first. On error nothing is changed.
+pub fn configure(config: &UartConfig) -> Result<(), ConfigError> {
+    let core_clock_hz = mailbox::clock_rate(clock::CORE).unwrap_or(with_state(|state| state.core_clock_hz));
+    apply(config, core_clock_hz)
+}
+
+/// Line settings currently in use.
+pub fn config() -> UartConfig {
+    with_state(|state| state.config)
+}
+
+/// Recompute the baud rate divisor for a new core clock. Call this (or `sync_core_clock`)
+/// whenever the core clock is changed (the baud rate changes with it otherwise). Fails, leaving the divisor unchanged, if the
+/// current baud rate cannot be generated from the new clock.
+pub fn set_core_clock(core_clock_hz: u32) -> Result<(), ConfigError> {
+    let (config, current) = with_state(|state| (state.config, state.core_clock_hz));
+    if core_clock_hz == current {
+        return Ok(());
     }
+    apply(&config, core_clock_hz)
 }
 
-pub fn write_byte(byte: u8) {
-    unsafe {
-        let mini_uart_regs = &mut *MINI_UART_REGS;
-        while !is_bit_set(mini_uart_regs.aux_mu_lsr_reg, 5) {} // Wait for TX FIFO to have space
-        mini_uart_regs.aux_mu_io_reg = byte as u32; // Write byte to TX FIFO
+/// Ask the firmware for the current core clock and recompute the divisor if it changed.
+/// Keeps the current divisor if the firmware does not answer.
+pub fn sync_core_clock() -> Result<(), ConfigError> {
+    match mailbox::clock_rate(clock::CORE) {
+        Some(core_clock_hz) => set_core_clock(core_clock_hz),
+        None => Ok(()),
+    }
+}
+
+/// Baud rate register value for `config` from a core clock of `core_clock_hz`.
+///
+/// Baudrate = core_clock / (8 * (baud_reg + 1)), e.g. 270 for 115200 baud @ 250MHz, 542 @ 500MHz.
+/// Fails if the closest value is more than `MAX_BAUD_ERROR` off, or for a frame format the Mini
+/// UART does not support (it only knows 7 or 8 data bits, no parity, one stop bit; its flow control
+/// is not implemented by this driver).
+pub fn divisor(core_clock_hz: u32, config: &UartConfig) -> Result<u32, ConfigError> {
+    if config.parity != Parity::None {
+        return Err(ConfigError::Unsupported("parity"));
+    }
+    if config.stop_bits != StopBits::One {
+        return Err(ConfigError::Unsupported("two stop bits"));
+    }
+    if config.flow_control != FlowControl::None {
+        return Err(ConfigError::Unsupported("flow control")); // Not implemented for the Mini UART
+    }
+    if matches!(config.data_bits, DataBits::Five | DataBits::Six) {
+        return Err(ConfigError::Unsupported("less than 7 data bits"));
     }
+    let baud = config.baud;
+    if baud == 0 {
+        return Err(ConfigError::BaudRate { requested: 0, achieved: 0 });
+    }
+    let clock = core_clock_hz as u64 / 8;
+    let baud_reg = ((clock + baud as u64 / 2) / baud as u64).clamp(1, 0x1_0000) - 1;
+    let achieved = (clock / (baud_reg + 1)) as u32;
+    ConfigError::check_baud(baud, achieved)?;
+    Ok(baud_reg as u32)
+}
+
+/// Write new settings: wait until the transmitter is idle, then change them with RX/TX disabled.
+fn apply(config: &UartConfig, core_clock_hz: u32) -> Result<(), ConfigError> {
+    let baud_reg = divisor(core_clock_hz, config)?;
+    flush();
+    let regs = mini_uart_regs();
+    let control = regs.aux_mu_cntl_reg.get();
+    regs.aux_mu_cntl_reg.set(0);
+    regs.aux_mu_baud_reg.write(AUX_MU_BAUD::BAUDRATE.val(baud_reg));
+    regs.aux_mu_lcr_reg.modify(data_size(config));
+    regs.aux_mu_cntl_reg.set(control);
+    with_state(|state| {
+        state.config = *config;
+        state.core_clock_hz = core_clock_hz;
+    });
+    Ok(())
+}
+
+/// Data size field for `config` (checked by `divisor`).
+fn data_size(config: &UartConfig) -> FieldValue<AUX_MU_LCR::Register> {
+    match config.data_bits {
+        DataBits::Seven => AUX_MU_LCR::DATA_SIZE::SevenBit,
+        _ => AUX_MU_LCR::DATA_SIZE::EightBit,
+    }
+}
+
+pub fn write_byte(byte: u8) {
+    let regs = mini_uart_regs();
+    while !AUX_MU_LSR::TX_EMPTY.is_set(line_status()) {} // Wait for TX FIFO to have space
+    regs.aux_mu_io_reg.write(AUX_MU_IO::DATA.val(byte as u32)); // Write byte to TX FIFO
 }
 
 pub fn write_string(s: &str) {
@@ -53,54 +182,181 @@ pub fn write_string(s: &str) {
     }
 }
 
+/// Read a byte if one is available (an overrun is only counted, see `try_read_byte`).
 pub fn read_byte() -> Option<u8> {
-    unsafe {
-        let mini_uart_regs = &*MINI_UART_REGS;
-        if is_bit_set(mini_uart_regs.aux_mu_lsr_reg, 0) { // Data ready in RX FIFO?
-            Some((mini_uart_regs.aux_mu_io_reg & 0xFF) as u8) // Read byte
-        } else {
-            None // No data available
+    loop {
+        match try_read_byte()? {
+            Ok(byte) => return Some(byte),
+            Err(_) => continue, // Overrun: the bytes still in the FIFO are fine
         }
     }
 }
 
-pub fn flush() {
-    unsafe {
-        let mini_uart_regs = &*MINI_UART_REGS;
-        while !is_bit_set(mini_uart_regs.aux_mu_lsr_reg, 6) {} // Wait for transmitter to be idle
-    }
-}
-
-pub fn read_line(buffer: &mut [u8]) -> Option<usize> {
-    let mut pos = 0;
-    while pos < buffer.len() - 1 {
-        if let Some(byte) = read_byte() {
-            match byte {
-                b'\r' | b'\n' => { // End of line
-                    buffer[pos] = 0; // Null terminate
-                    return Some(pos);
-                }
-                b'\x08' | b'\x7f' => { // Backspace or DEL
-                    if pos > 0 {
-                        pos -= 1;
-                        write_string("\x08 \x08"); // Erase last char on terminal
-                    }
-                }
-                _ => {
-                    buffer[pos] = byte; // Store byte
-                    write_byte(byte);   // Echo back
-                    pos += 1;
-                }
-            }
-        }
+/// Read a byte if one is available. An overrun is reported once, ahead of the bytes that were
+/// received before it (they are intact, the bytes after them were lost).
+pub fn try_read_byte() -> Option<Result<u8, UartError>> {
+    let status = line_status();
+    if with_state(|state| core::mem::take(&mut state.overrun_pending)) {
+        return Some(Err(UartError::Overrun));
+    }
+    if AUX_MU_LSR::DATA_READY.is_set(status) { // Data ready in RX FIFO?
+        Some(Ok(mini_uart_regs().aux_mu_io_reg.read(AUX_MU_IO::DATA) as u8)) // Read byte
+    } else {
+        None // No data available
     }
-    buffer[pos] = 0; // Null terminate
-    Some(pos)
+}
+
+/// Receive errors counted since `init` (or `reset_error_counts`).
+pub fn error_counts() -> ErrorCounts {
+    with_state(|state| state.errors)
+}
+
+pub fn reset_error_counts() {
+    with_state(|state| state.errors = ErrorCounts::new());
+}
+
+/// Send a break: hold TX low for `duration_us`, but at least two frames. Pending output is sent first.
+pub fn send_break(duration_us: u32) {
+    flush();
+    let regs = mini_uart_regs();
+    regs.aux_mu_lcr_reg.modify(AUX_MU_LCR::BREAK::SET);
+    timer::delay_us(duration_us.max(2 * config().frame_time_us()));
+    regs.aux_mu_lcr_reg.modify(AUX_MU_LCR::BREAK::CLEAR);
+}
+
+pub fn flush() {
+    while !AUX_MU_LSR::TX_IDLE.is_set(line_status()) {} // Wait for transmitter to be idle
+}
+
+/// Read a line with editing (see `line_editor`).
+pub fn read_line(buffer: &mut [u8]) -> Result<&str, LineError> {
+    line_editor::read_line(&MiniUartSerial, buffer)
+}
+
+/// Like `read_line`, but give up after `timeout_us` microseconds without Enter.
+pub fn read_line_timeout(buffer: &mut [u8], timeout_us: u32) -> Result<&str, LineError> {
+    line_editor::read_line_timeout(&MiniUartSerial, buffer, timeout_us)
+}
+
+/// Wait up to `timeout_us` microseconds for a byte.
+pub fn read_byte_timeout(timeout_us: u32) -> Result<u8, ReadError> {
+    MiniUartSerial.read_byte_timeout(timeout_us)
+}
+
+/// Fill `buffer` within `timeout_us` microseconds (see `SerialPort::read_exact_timeout`).
+pub fn read_exact_timeout(buffer: &mut [u8], timeout_us: u32) -> Result<(), ReadError> {
+    MiniUartSerial.read_exact_timeout(buffer, timeout_us)
 }
 
 pub fn is_data_ready() -> bool {
-    unsafe {
-        let mini_uart_regs = &*MINI_UART_REGS;
-        is_bit_set(mini_uart_regs.aux_mu_lsr_reg, 0) // RX FIFO has data?
+    AUX_MU_LSR::DATA_READY.is_set(line_status()) // RX FIFO has data?
+}
+
+/// Read the line status register. Reading clears the overrun flag, so it is recorded here.
+fn line_status() -> u32 {
+    let status = mini_uart_regs().aux_mu_lsr_reg.get();
+    if AUX_MU_LSR::RX_OVERRUN.is_set(status) {
+        with_state(|state| {
+            state.errors.record(UartError::Overrun);
+            state.overrun_pending = true;
+        });
+    }
+    status
+}
+
+/// Run `f` with the driver state.
+#[cfg(not(test))]
+fn with_state<T>(f: impl FnOnce(&mut State) -> T) -> T {
+    static mut STATE: State = State::INITIAL;
+    // Safety: single core, and the Mini UART is only used from thread context (never from interrupts).
+    f(unsafe { &mut *core::ptr::addr_of_mut!(STATE) })
+}
+
+/// Run `f` with the driver state. Host tests get one state per test thread.
+#[cfg(test)]
+fn with_state<T>(f: impl FnOnce(&mut State) -> T) -> T {
+    extern crate std;
+    std::thread_local! {
+        static STATE: core::cell::RefCell<State> = const { core::cell::RefCell::new(State::INITIAL) };
+    }
+    STATE.with(|state| f(&mut state.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Soc;
+    use crate::drivers::peripherals::Peripherals;
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::mailbox::MAILBOX_REGS_OFFSET;
+    use crate::hal::registers::uart::MINI_UART_REGS_OFFSET;
+
+    const MBOX_STATUS: usize = MAILBOX_REGS_OFFSET + 0x18;
+    const AUX_MU_IO: usize = MINI_UART_REGS_OFFSET;
+    const AUX_MU_LSR: usize = MINI_UART_REGS_OFFSET + 0x14;
+    const AUX_MU_BAUD: usize = MINI_UART_REGS_OFFSET + 0x28;
+
+    #[test]
+    fn the_divisor_follows_the_core_clock() {
+        assert_eq!(divisor(250_000_000, &UartConfig::new(115200)), Ok(270));
+        assert_eq!(divisor(500_000_000, &UartConfig::new(115200)), Ok(542));
+        assert_eq!(divisor(250_000_000, &UartConfig::new(300)), Err(ConfigError::BaudRate { requested: 300, achieved: 476 }));
+        let even = UartConfig { parity: Parity::Even, ..UartConfig::default() };
+        assert_eq!(divisor(250_000_000, &even), Err(ConfigError::Unsupported("parity")));
+
+        sim::reset();
+        sim::poke(MBOX_STATUS, 1 << 30); // No firmware: the mailbox stays empty
+        init(unsafe { Peripherals::steal() }.mini_uart, &UartConfig::default()).unwrap();
+        let expected = match board::current().soc {
+            Soc::Bcm2711 => 542, // 500 MHz
+            Soc::Bcm2837 => 270, // 250 MHz
+        };
+        assert_eq!(sim::last_write(AUX_MU_BAUD), Some(expected)); // The board value
+        sim::poke(AUX_MU_LSR, 1 << 6); // Transmitter idle
+        set_core_clock(200_000_000).unwrap(); // Frequency scaling
+        assert_eq!(sim::last_write(AUX_MU_BAUD), Some(216)); // 200 MHz / (8 * 217) = 115207 baud
+        assert_eq!(set_core_clock(1_000_000), Err(ConfigError::BaudRate { requested: 115200, achieved: 125000 }));
+        assert_eq!(sim::last_write(AUX_MU_BAUD), Some(216));
+    }
+
+    #[test]
+    fn overruns_are_reported_before_the_surviving_bytes() {
+        sim::reset();
+        sim::poke(MBOX_STATUS, 1 << 30); // No firmware: the mailbox stays empty
+        init(unsafe { Peripherals::steal() }.mini_uart, &UartConfig::default()).unwrap();
+        sim::poke(AUX_MU_IO, b'A' as u32);
+        sim::poke(AUX_MU_LSR, (1 << 1) | 1); // Overrun, data ready
+        assert!(is_data_ready()); // Reads (and clears) the overrun flag: still counted
+        sim::poke(AUX_MU_LSR, 1);
+        assert_eq!(try_read_byte(), Some(Err(UartError::Overrun)));
+        assert_eq!(try_read_byte(), Some(Ok(b'A')));
+        assert_eq!(error_counts().overruns, 1);
+        sim::poke(AUX_MU_LSR, 0);
+        assert_eq!(read_byte(), None);
+    }
+
+    #[test]
+    fn timed_reads_give_up_on_a_silent_line() {
+        // 1 MHz counter advancing 1 us per read: every poll takes 1 us
+        crate::drivers::timer::sim::set(1_000_000, 1);
+        sim::reset();
+        sim::poke(MBOX_STATUS, 1 << 30); // No firmware: the mailbox stays empty
+        init(unsafe { Peripherals::steal() }.mini_uart, &UartConfig::default()).unwrap();
+        sim::poke(AUX_MU_IO, b'A' as u32);
+        sim::poke(AUX_MU_LSR, 1); // Data ready
+        let mut reply = [0u8; 3];
+        assert_eq!(read_exact_timeout(&mut reply, 1000), Ok(()));
+        assert_eq!(reply, *b"AAA");
+        sim::poke(AUX_MU_LSR, (1 << 1) | 1); // Overrun, data ready
+        assert_eq!(read_byte_timeout(1000), Err(ReadError::Uart(UartError::Overrun)));
+        sim::poke(AUX_MU_LSR, 1 << 5); // Nothing received, room to echo
+        let polls = || {
+            let trace = sim::trace();
+            trace.iter().filter(|access| matches!(access, sim::Access::Read { offset: AUX_MU_LSR, .. })).count()
+        };
+        let before = polls();
+        assert_eq!(read_byte_timeout(1000), Err(ReadError::Timeout));
+        assert_eq!(polls() - before, 1000); // Kept polling until the deadline
+        assert_eq!(read_line_timeout(&mut [0u8; 16], 1000), Err(LineError::Timeout));
     }
 }
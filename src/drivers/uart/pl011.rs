@@ -0,0 +1,780 @@
+//! PL011 UART driver, for every PL011 instance of the SoC
+//!
+//! | Instance | TX     | RX     | CTS    | RTS    | Boards          |
+//! |----------|--------|--------|--------|--------|-----------------|
+//! | UART0    | GPIO14 | GPIO15 | GPIO16 | GPIO17 | all             |
+//! | UART2    | GPIO0  | GPIO1  | GPIO2  | GPIO3  | BCM2711 (Pi 4)  |
+//! | UART3    | GPIO4  | GPIO5  | GPIO6  | GPIO7  | BCM2711 (Pi 4)  |
+//! | UART4    | GPIO8  | GPIO9  | GPIO10 | GPIO11 | BCM2711 (Pi 4)  |
+//! | UART5    | GPIO12 | GPIO13 | GPIO14 | GPIO15 | BCM2711 (Pi 4)  |
+//!
+//! The CTS/RTS pins are only claimed when the configuration asks for flow control
+//! ([`FlowControl`]); UART5's handshake pins are UART0's TX/RX.
+//!
+//! A [`Pl011`] is created from the ownership token of its instance (see `drivers::peripherals`),
+//! claims the pins and routes the signals to them (the pinmux table picks the alternate function).
+//! Each instance has its own line settings and its own RX/TX ring buffers in interrupt mode; all
+//! instances share the one UART interrupt of the SoC.
+//!
+//! `uart0` is UART0 through this driver, as module-level functions for the console.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::uart::{Pl011, UartConfig};
+//!
+//! let peripherals = Peripherals::take().unwrap();
+//! let gps = Pl011::new(peripherals.uart3, &UartConfig::new(9600))?;    // GPIO4/5
+//! let motor = Pl011::new(peripherals.uart5, &UartConfig::new(57600))?; // GPIO12/13
+//! gps.enable_interrupts();
+//! motor.write_string("M1 100\r\n");
+//! while let Some(byte) = gps.read_byte() { /* NMEA parser */ }
+//!
+//! let rs485 = UartConfig { flow_control: FlowControl::ManualRts, ..UartConfig::new(9600) };
+//! let bus = Pl011::new(peripherals.uart4, &rs485)?; // GPIO8/9, RTS4 on GPIO11 drives DE
+//! bus.set_rts(true);
+//! bus.write_string("\x01\x03\x00\x00\x00\x01");
+//! bus.flush();
+//! bus.set_rts(false); // Back to receiving
+//! ```
+
+use crate::board::{self, Soc};
+use crate::drivers::gpio::{self, Pull};
+use crate::drivers::peripherals::{Uart0, Uart2, Uart3, Uart4, Uart5};
+use crate::drivers::pins;
+use crate::drivers::timer;
+use crate::hal::registers::access::{Field, FieldValue};
+use crate::hal::registers::uart::{
+    pl011_uart2_regs, pl011_uart3_regs, pl011_uart4_regs, pl011_uart5_regs, pl011_uart_regs,
+    Pl011UartRegisters, CR, DR, FBRD, FR, IBRD, IFLS, INT, LCRH,
+};
+use crate::interrupts::{self, irq, Handler};
+
+use super::config::{ConfigError, DataBits, FlowControl, InitError, Parity, StopBits, UartConfig};
+use super::error::{ErrorCounts, ReadError, UartError};
+use super::line_editor::{self, LineError};
+use super::ring_buffer::RingBuffer;
+use super::serial_port::SerialPort;
+
+/// Size of the software RX and TX buffers used in interrupt mode.
+const BUFFER_SIZE: usize = 256;
+
+/// Depth of the PL011 hardware FIFOs. Bounds the work done per interrupt.
+const FIFO_DEPTH: usize = 16;
+
+/// Description of one PL011 instance.
+struct Instance {
+    /// Name under which the instance claims its pins.
+    name: &'static str,
+    /// Register block accessor.
+    regs: fn() -> &'static Pl011UartRegisters,
+    /// TX pin and signal.
+    tx: (u8, &'static str),
+    /// RX pin and signal.
+    rx: (u8, &'static str),
+    /// CTS and RTS pins and signals, used with flow control.
+    handshake: [(u8, &'static str); 2],
+    /// Only the BCM2711 has UART2-5.
+    bcm2711_only: bool,
+}
+
+/// All PL011 instances, indexed by `Pl011::index`.
+#[rustfmt::skip]
+static INSTANCES: [Instance; 5] = [
+    Instance { name: "uart0", regs: pl011_uart_regs,  tx: (14, "TXD0"), rx: (15, "RXD0"), handshake: [(16, "CTS0"), (17, "RTS0")], bcm2711_only: false },
+    Instance { name: "uart2", regs: pl011_uart2_regs, tx: (0, "TXD2"),  rx: (1, "RXD2"),  handshake: [(2, "CTS2"), (3, "RTS2")],   bcm2711_only: true },
+    Instance { name: "uart3", regs: pl011_uart3_regs, tx: (4, "TXD3"),  rx: (5, "RXD3"),  handshake: [(6, "CTS3"), (7, "RTS3")],   bcm2711_only: true },
+    Instance { name: "uart4", regs: pl011_uart4_regs, tx: (8, "TXD4"),  rx: (9, "RXD4"),  handshake: [(10, "CTS4"), (11, "RTS4")], bcm2711_only: true },
+    Instance { name: "uart5", regs: pl011_uart5_regs, tx: (12, "TXD5"), rx: (13, "RXD5"), handshake: [(14, "CTS5"), (15, "RTS5")], bcm2711_only: true },
+];
+
+impl Instance {
+    /// Handshake pins used with `flow_control`.
+    fn flow_pins(&self, flow_control: FlowControl) -> &[(u8, &'static str)] {
+        match flow_control {
+            FlowControl::None => &[],
+            FlowControl::RtsCts => &self.handshake,
+            FlowControl::ManualRts => &self.handshake[1..],
+        }
+    }
+}
+
+/// Ownership token of a PL011 instance.
+pub trait Pl011Token {
+    /// Index into the instance table.
+    #[doc(hidden)]
+    const INDEX: usize;
+}
+
+impl Pl011Token for Uart0 {
+    const INDEX: usize = 0;
+}
+impl Pl011Token for Uart2 {
+    const INDEX: usize = 1;
+}
+impl Pl011Token for Uart3 {
+    const INDEX: usize = 2;
+}
+impl Pl011Token for Uart4 {
+    const INDEX: usize = 3;
+}
+impl Pl011Token for Uart5 {
+    const INDEX: usize = 4;
+}
+
+/// Receive error flags of a DR word, most severe first: a break also sets FE, so it is reported
+/// (and counted) as a break only.
+const RX_ERRORS: [(Field<DR::Register>, UartError); 4] = [
+    (DR::BE, UartError::Break),
+    (DR::FE, UartError::Framing),
+    (DR::PE, UartError::Parity),
+    (DR::OE, UartError::Overrun),
+];
+
+/// Line settings, receive errors, breaks and state of the interrupt-driven mode of one instance.
+struct State {
+    config: UartConfig,
+    errors: ErrorCounts,
+    /// A break was received since the last `break_received`.
+    break_received: bool,
+    /// Run from the UART interrupt when a break arrives.
+    break_handler: Option<Handler>,
+    interrupt_mode: bool,
+    rx: RingBuffer<BUFFER_SIZE>,
+    /// Error flags (DR bits 11:8) of each byte in `rx`.
+    rx_flags: RingBuffer<BUFFER_SIZE>,
+    /// Bytes were dropped because `rx` was full: the next byte stored gets the overrun flag.
+    rx_overrun: bool,
+    tx: RingBuffer<BUFFER_SIZE>,
+}
+
+impl State {
+    const INITIAL: State = State {
+        config: UartConfig::new(115200),
+        errors: ErrorCounts::new(),
+        break_received: false,
+        break_handler: None,
+        interrupt_mode: false,
+        rx: RingBuffer::new(),
+        rx_flags: RingBuffer::new(),
+        rx_overrun: false,
+        tx: RingBuffer::new(),
+    };
+}
+
+/// An initialized PL011 UART.
+pub struct Pl011 {
+    index: usize,
+}
+
+impl Pl011 {
+    /// UART0, for the `uart0` module functions (which are only used after `uart0::init`).
+    pub(super) const UART0: Pl011 = Pl011 { index: 0 };
+
+    /// Initialize the instance of `token` on its TX/RX pins with the given line settings.
+    /// Fails if the instance does not exist on this board, another driver owns one of the pins or
+    /// the baud rate cannot be generated.
+    pub fn new<T: Pl011Token>(_token: T, config: &UartConfig) -> Result<Pl011, InitError> {
+        let uart = Pl011 { index: T::INDEX };
+        let instance = uart.instance();
+        if instance.bcm2711_only && board::current().soc != Soc::Bcm2711 {
+            return Err(InitError::Unavailable(instance.name));
+        }
+        let (ibrd, fbrd) = divisors(board::current().uart_clock_hz, config.baud)?; // Before claiming anything
+        // Claim the TX and RX pins (and the handshake pins, if used) and route the UART signals to them
+        let flow_pins = instance.flow_pins(config.flow_control);
+        let mut signals = [instance.tx, instance.rx, (0, ""), (0, "")];
+        signals[2..2 + flow_pins.len()].copy_from_slice(flow_pins);
+        gpio::request_signals(&signals[..2 + flow_pins.len()], instance.name)?;
+        gpio::set_pull(instance.tx.0, Pull::None); // TX is driven by the UART
+        gpio::set_pull(instance.rx.0, Pull::Up); // Keep RX idle (high) when nothing is connected, no spurious bytes
+        uart.set_handshake_pulls(config.flow_control);
+        let regs = uart.regs();
+        regs.cr.set(0); // Disable the UART before config
+        regs.icr.write(INT::ALL::SET); // Clear all pending interrupts
+        uart.write_line_settings(ibrd, fbrd, config);
+        // Enable UART, TX, RX (and the hardware handshake)
+        regs.cr.write(CR::UARTEN::SET + CR::TXE::SET + CR::RXE::SET + handshake(config.flow_control));
+        uart.with_state(|state| *state = State { config: *config, ..State::INITIAL });
+        Ok(uart)
+    }
+
+    /// Name of the instance ("uart0", "uart2", ...).
+    pub fn name(&self) -> &'static str {
+        self.instance().name
+    }
+
+    /// Apply new line settings at runtime. Pending output is sent with the old settings first.
+    /// On error nothing is changed.
+    pub fn configure(&self, config: &UartConfig) -> Result<(), ConfigError> {
+        let (ibrd, fbrd) = divisors(board::current().uart_clock_hz, config.baud)?;
+        let previous = self.config().flow_control;
+        if config.flow_control != previous {
+            self.switch_flow_pins(previous, config.flow_control)?;
+        }
+        self.flush();
+        let regs = self.regs();
+        let control = handshake(config.flow_control).modify(regs.cr.get());
+        // The PL011 must be disabled and idle while its line settings change
+        regs.cr.set(CR::UARTEN::CLEAR.modify(control));
+        while regs.fr.is_set(FR::BUSY) {}
+        self.write_line_settings(ibrd, fbrd, config);
+        regs.cr.set(control);
+        interrupts::without_interrupts(|| self.with_state(|state| state.config = *config));
+        Ok(())
+    }
+
+    /// Line settings currently in use.
+    pub fn config(&self) -> UartConfig {
+        self.with_state(|state| state.config)
+    }
+
+    /// Assert (drive low) or deassert (drive high) RTS. Only reaches the pin with
+    /// `FlowControl::ManualRts`; with `RtsCts` the UART drives RTS itself.
+    pub fn set_rts(&self, asserted: bool) {
+        let rts = if asserted { CR::RTS::SET } else { CR::RTS::CLEAR };
+        self.regs().cr.modify(rts);
+    }
+
+    /// Check whether the other end asserts CTS (allows us to send).
+    pub fn is_cts_asserted(&self) -> bool {
+        self.regs().fr.is_set(FR::CTS)
+    }
+
+    /// Send a break: hold TX low for `duration_us`, but at least two frames so the other end
+    /// notices. Pending output is sent first.
+    pub fn send_break(&self, duration_us: u32) {
+        self.flush();
+        let regs = self.regs();
+        while regs.fr.is_set(FR::BUSY) {} // Let the last byte leave the shift register
+        regs.lcrh.modify(LCRH::BRK::SET);
+        timer::delay_us(duration_us.max(2 * self.config().frame_time_us()));
+        regs.lcrh.modify(LCRH::BRK::CLEAR);
+    }
+
+    /// Check whether a break was received since the last call (it arrives as a `UartError::Break`
+    /// byte in the received data too).
+    pub fn break_received(&self) -> bool {
+        interrupts::without_interrupts(|| self.with_state(|state| core::mem::take(&mut state.break_received)))
+    }
+
+    /// Run `handler` from the UART interrupt whenever a break arrives (interrupt mode only), e.g.
+    /// to enter a debug menu. The handler runs in interrupt context with IRQs masked.
+    pub fn set_break_handler(&self, handler: Option<Handler>) {
+        interrupts::without_interrupts(|| self.with_state(|state| state.break_handler = handler));
+    }
+
+    /// Switch to interrupt-driven mode: RX/TX through ring buffers, moved by the UART interrupt.
+    /// Call after `interrupts::init`; the bytes only move once IRQs are unmasked.
+    pub fn enable_interrupts(&self) {
+        let regs = self.regs();
+        interrupts::without_interrupts(|| {
+            self.with_state(|state| {
+                state.rx.clear();
+                state.rx_flags.clear();
+                state.rx_overrun = false;
+                state.tx.clear();
+                state.interrupt_mode = true;
+            });
+            // RX interrupt at 1/2 full, plus the receive timeout for the bytes below that level.
+            // TX interrupt when the FIFO drains to 1/8 full; only unmasked while bytes are queued.
+            regs.ifls.write(IFLS::RXIFLSEL::OneHalf + IFLS::TXIFLSEL::OneEighth);
+            regs.icr.write(INT::ALL::SET);
+            regs.imsc.write(INT::RX::SET + INT::RT::SET);
+        });
+        interrupts::register(irq::UART, Pl011::handle_interrupt);
+    }
+
+    /// Go back to polled mode. Queued TX bytes are sent first; unread RX bytes are dropped.
+    pub fn disable_interrupts(&self) {
+        self.flush();
+        let regs = self.regs();
+        let others = interrupts::without_interrupts(|| {
+            regs.imsc.set(0);
+            self.with_state(|state| state.interrupt_mode = false);
+            with_states(|states| states.iter().any(|state| state.interrupt_mode))
+        });
+        if !others {
+            interrupts::unregister(irq::UART); // Shared by all instances
+        }
+    }
+
+    /// Check whether this UART runs interrupt-driven.
+    pub fn interrupts_enabled(&self) -> bool {
+        self.with_state(|state| state.interrupt_mode)
+    }
+
+    pub fn write_byte(&self, byte: u8) {
+        let regs = self.regs();
+        let queued = interrupts::without_interrupts(|| {
+            self.with_state(|state| {
+                if !state.interrupt_mode {
+                    return false;
+                }
+                // The TX interrupt fires when the FIFO drains below its level, so keep feeding the
+                // FIFO directly while it has room and only queue behind a full FIFO.
+                if state.tx.is_empty() && !regs.fr.is_set(FR::TXFF) {
+                    regs.dr.set(byte as u32);
+                    return true;
+                }
+                if state.tx.is_full() {
+                    // Queue full (or called with IRQs masked): make room by sending the oldest byte
+                    while regs.fr.is_set(FR::TXFF) {}
+                    if let Some(oldest) = state.tx.pop() {
+                        regs.dr.set(oldest as u32);
+                    }
+                }
+                state.tx.push(byte);
+                regs.imsc.modify(INT::TX::SET);
+                true
+            })
+        });
+        if !queued {
+            while regs.fr.is_set(FR::TXFF) {} // Wait for TX FIFO to have space
+            regs.dr.set(byte as u32); // Write byte to TX FIFO
+        }
+    }
+
+    pub fn write_string(&self, s: &str) {
+        for byte in s.bytes() {
+            self.write_byte(byte); // Send each byte
+        }
+    }
+
+    /// Read a byte if one is available. Bytes received with an error are dropped (they are still
+    /// counted); use `try_read_byte` to see them.
+    pub fn read_byte(&self) -> Option<u8> {
+        loop {
+            match self.try_read_byte()? {
+                Ok(byte) => return Some(byte),
+                Err(_) => continue, // Damaged: try the next one
+            }
+        }
+    }
+
+    /// Read a byte if one is available, or the error it was received with.
+    pub fn try_read_byte(&self) -> Option<Result<u8, UartError>> {
+        self.next_word().map(decode)
+    }
+
+    /// Receive errors counted since `new` (or `reset_error_counts`).
+    pub fn error_counts(&self) -> ErrorCounts {
+        interrupts::without_interrupts(|| self.with_state(|state| state.errors))
+    }
+
+    pub fn reset_error_counts(&self) {
+        interrupts::without_interrupts(|| self.with_state(|state| state.errors = ErrorCounts::new()));
+    }
+
+    pub fn flush(&self) {
+        let regs = self.regs();
+        // Drain the TX queue by polling: this also works with IRQs masked (e.g. from a panic)
+        while let Some(byte) = interrupts::without_interrupts(|| self.with_state(|state| state.tx.pop())) {
+            while regs.fr.is_set(FR::TXFF) {}
+            regs.dr.set(byte as u32);
+        }
+        while !regs.fr.is_set(FR::TXFE) {} // Wait for TX FIFO to be empty
+    }
+
+    /// Read a line with editing (see `line_editor`; use a `LineEditor` for history and completion).
+    pub fn read_line<'a>(&self, buffer: &'a mut [u8]) -> Result<&'a str, LineError> {
+        line_editor::read_line(self, buffer)
+    }
+
+    /// Like `read_line`, but give up after `timeout_us` microseconds without Enter.
+    pub fn read_line_timeout<'a>(&self, buffer: &'a mut [u8], timeout_us: u32) -> Result<&'a str, LineError> {
+        line_editor::read_line_timeout(self, buffer, timeout_us)
+    }
+
+    /// Wait up to `timeout_us` microseconds for a byte.
+    pub fn read_byte_timeout(&self, timeout_us: u32) -> Result<u8, ReadError> {
+        SerialPort::read_byte_timeout(self, timeout_us)
+    }
+
+    /// Fill `buffer` within `timeout_us` microseconds (see `SerialPort::read_exact_timeout`).
+    pub fn read_exact_timeout(&self, buffer: &mut [u8], timeout_us: u32) -> Result<(), ReadError> {
+        SerialPort::read_exact_timeout(self, buffer, timeout_us)
+    }
+
+    pub fn is_data_ready(&self) -> bool {
+        if self.interrupts_enabled() {
+            return interrupts::without_interrupts(|| self.with_state(|state| !state.rx.is_empty()));
+        }
+        let regs = self.regs();
+        !regs.fr.is_set(FR::RXFE) // RX FIFO has data?
+    }
+
+    /// UART interrupt handler (registered by `enable_interrupts`), shared by all instances: move
+    /// received bytes into the RX buffers and queued bytes into the TX FIFOs.
+    pub fn handle_interrupt() {
+        for index in 0..INSTANCES.len() {
+            let uart = Pl011 { index };
+            if uart.interrupts_enabled() {
+                uart.handle_instance_interrupt();
+            }
+        }
+    }
+
+    /// Interrupt handling of this instance.
+    fn handle_instance_interrupt(&self) {
+        let regs = self.regs();
+        let pending = regs.mis.get();
+        if pending == 0 {
+            return;
+        }
+        let on_break = self.with_state(|state| {
+            let mut got_break = false;
+            if pending & (INT::RX::SET.value | INT::RT::SET.value) != 0 {
+                // Reading the FIFO below its level clears RX and RT
+                for _ in 0..FIFO_DEPTH {
+                    if regs.fr.is_set(FR::RXFE) {
+                        break;
+                    }
+                    let word = receive(regs, state);
+                    got_break |= DR::BE.is_set(word);
+                    // A full buffer drops the byte: nobody has read the last BUFFER_SIZE bytes.
+                    // The loss is reported with the next byte that fits, like the OE flag of the FIFO.
+                    if state.rx.push(word as u8) {
+                        let overrun = if core::mem::take(&mut state.rx_overrun) { DR::OE::SET.value } else { 0 };
+                        state.rx_flags.push(((word | overrun) >> 8) as u8);
+                    } else {
+                        state.errors.record(UartError::Overrun);
+                        state.rx_overrun = true;
+                    }
+                }
+            }
+            if pending & INT::TX::SET.value != 0 {
+                while !regs.fr.is_set(FR::TXFF) {
+                    match state.tx.pop() {
+                        Some(byte) => regs.dr.set(byte as u32),
+                        None => break,
+                    }
+                }
+                if state.tx.is_empty() {
+                    regs.imsc.modify(INT::TX::CLEAR); // Nothing left to send
+                }
+            }
+            state.break_handler.filter(|_| got_break)
+        });
+        regs.icr.set(pending); // Clear what we handled (and any error interrupts)
+        if let Some(handler) = on_break {
+            handler(); // Outside the state: the handler may use this UART
+        }
+    }
+
+    /// Next received DR word (data and error flags), from the RX buffer in interrupt mode.
+    fn next_word(&self) -> Option<u32> {
+        if self.interrupts_enabled() {
+            return interrupts::without_interrupts(|| {
+                self.with_state(|state| {
+                    let byte = state.rx.pop()?;
+                    let flags = state.rx_flags.pop().unwrap_or(0);
+                    Some((flags as u32) << 8 | byte as u32)
+                })
+            });
+        }
+        let regs = self.regs();
+        if regs.fr.is_set(FR::RXFE) { // RX FIFO empty?
+            return None; // No data available
+        }
+        Some(self.with_state(|state| receive(regs, state)))
+    }
+
+    /// Claim the handshake pins of `next` and release the ones only `previous` used.
+    fn switch_flow_pins(&self, previous: FlowControl, next: FlowControl) -> Result<(), ConfigError> {
+        let instance = self.instance();
+        gpio::request_signals(instance.flow_pins(next), instance.name)?;
+        for &(pin, _) in instance.flow_pins(previous) {
+            if !instance.flow_pins(next).iter().any(|&(next_pin, _)| next_pin == pin) {
+                pins::release(pin, instance.name);
+            }
+        }
+        self.set_handshake_pulls(next);
+        Ok(())
+    }
+
+    /// Pulls of the handshake pins in use: RTS is driven by the UART, and CTS is pulled low
+    /// (asserted) so an unconnected CTS does not stall the transmitter forever.
+    fn set_handshake_pulls(&self, flow_control: FlowControl) {
+        let [cts, rts] = self.instance().handshake;
+        match flow_control {
+            FlowControl::None => {}
+            FlowControl::RtsCts => {
+                gpio::set_pull(cts.0, Pull::Down);
+                gpio::set_pull(rts.0, Pull::None);
+            }
+            FlowControl::ManualRts => gpio::set_pull(rts.0, Pull::None),
+        }
+    }
+
+    fn instance(&self) -> &'static Instance {
+        &INSTANCES[self.index]
+    }
+
+    fn regs(&self) -> &'static Pl011UartRegisters {
+        (self.instance().regs)()
+    }
+
+    /// Write the divisors and frame format. IBRD/FBRD only take effect with the following LCRH write.
+    fn write_line_settings(&self, ibrd: u32, fbrd: u32, config: &UartConfig) {
+        let regs = self.regs();
+        regs.ibrd.write(IBRD::BAUD_DIVINT.val(ibrd)); // Set integer baud rate divisor
+        regs.fbrd.write(FBRD::BAUD_DIVFRAC.val(fbrd)); // Set fractional baud rate divisor
+        let word_length = match config.data_bits {
+            DataBits::Five => LCRH::WLEN::Five,
+            DataBits::Six => LCRH::WLEN::Six,
+            DataBits::Seven => LCRH::WLEN::Seven,
+            DataBits::Eight => LCRH::WLEN::Eight,
+        };
+        let parity: FieldValue<LCRH::Register> = match config.parity {
+            Parity::None => LCRH::PEN::CLEAR,
+            Parity::Even => LCRH::PEN::SET + LCRH::EPS::SET,
+            Parity::Odd => LCRH::PEN::SET + LCRH::EPS::CLEAR,
+        };
+        let stop_bits = match config.stop_bits {
+            StopBits::One => LCRH::STP2::CLEAR,
+            StopBits::Two => LCRH::STP2::SET,
+        };
+        regs.lcrh.write(word_length + parity + stop_bits + LCRH::FEN::SET); // Frame format, enable FIFOs
+    }
+
+    /// Run `f` with the state of this instance.
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        with_states(|states| f(&mut states[self.index]))
+    }
+}
+
+/// Read one word (data and error flags) from the RX FIFO. Its errors are counted and cleared in
+/// RSRECR, so they do not stick to the bytes that follow.
+fn receive(regs: &Pl011UartRegisters, state: &mut State) -> u32 {
+    let word = regs.dr.get();
+    let mut flagged = false;
+    for (flag, error) in RX_ERRORS {
+        if flag.is_set(word) {
+            if !(error == UartError::Framing && DR::BE.is_set(word)) {
+                state.errors.record(error);
+            }
+            flagged = true;
+        }
+    }
+    state.break_received |= DR::BE.is_set(word);
+    if flagged {
+        regs.rsrecr.set(0); // Any write clears the error flags
+    }
+    word
+}
+
+/// The byte of a received DR word, or its most severe error.
+fn decode(word: u32) -> Result<u8, UartError> {
+    match RX_ERRORS.iter().find(|(flag, _)| flag.is_set(word)) {
+        Some(&(_, error)) => Err(error),
+        None => Ok(DR::DATA.read(word) as u8),
+    }
+}
+
+/// CR bits for `flow_control`: the hardware handshake (RTSEN, CTSEN) is only enabled for `RtsCts`.
+fn handshake(flow_control: FlowControl) -> FieldValue<CR::Register> {
+    match flow_control {
+        FlowControl::RtsCts => CR::RTSEN::SET + CR::CTSEN::SET,
+        FlowControl::None | FlowControl::ManualRts => CR::RTSEN::CLEAR + CR::CTSEN::CLEAR,
+    }
+}
+
+/// Integer and fractional baud rate divisors (IBRD, FBRD) for `baud` from a UART clock of `clock_hz`.
+///
+/// Divisor = UARTCLK / (16 * baud), as a 16.6 fixed point number (26.3 for 115200 baud @ 48MHz).
+/// Fails if the closest divisor is more than `MAX_BAUD_ERROR` off or out of range (1 to 65535).
+pub fn divisors(clock_hz: u32, baud: u32) -> Result<(u32, u32), ConfigError> {
+    const MIN: u64 = 1 << 6; // 1.0
+    const MAX: u64 = 0xFFFF << 6; // 65535.0
+    if baud == 0 {
+        return Err(ConfigError::BaudRate { requested: 0, achieved: 0 });
+    }
+    let clock = 4 * clock_hz as u64; // 64 * UARTCLK / 16
+    let divisor = ((clock + baud as u64 / 2) / baud as u64).clamp(MIN, MAX);
+    let achieved = ((clock + divisor / 2) / divisor) as u32;
+    ConfigError::check_baud(baud, achieved)?;
+    Ok(((divisor >> 6) as u32, (divisor & 0x3F) as u32))
+}
+
+/// Run `f` with the state of all instances. Callers outside the UART interrupt mask IRQs around it.
+#[cfg(not(test))]
+fn with_states<T>(f: impl FnOnce(&mut [State; INSTANCES.len()]) -> T) -> T {
+    static mut STATES: [State; INSTANCES.len()] = [State::INITIAL; INSTANCES.len()];
+    // Safety: single core. The state is only used with IRQs masked, either by `without_interrupts`
+    // or because we are the UART interrupt handler.
+    f(unsafe { &mut *core::ptr::addr_of_mut!(STATES) })
+}
+
+/// Run `f` with the state of all instances. Host tests get one table per test thread.
+#[cfg(test)]
+fn with_states<T>(f: impl FnOnce(&mut [State; INSTANCES.len()]) -> T) -> T {
+    extern crate std;
+    std::thread_local! {
+        static STATES: core::cell::RefCell<[State; INSTANCES.len()]> =
+            const { core::cell::RefCell::new([State::INITIAL; INSTANCES.len()]) };
+    }
+    STATES.with(|states| f(&mut states.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::peripherals::Peripherals;
+    use crate::drivers::pins::{self, PinError};
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::gpio::GPIO_REGS_OFFSET;
+    use crate::hal::registers::uart::{PL011_UART3_REGS_OFFSET, PL011_UART5_REGS_OFFSET, PL011_UART_REGS_OFFSET};
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    const GPFSEL0: usize = GPIO_REGS_OFFSET;
+    const GPFSEL1: usize = GPIO_REGS_OFFSET + 0x04;
+    const DR: usize = PL011_UART_REGS_OFFSET;
+    const RSRECR: usize = PL011_UART_REGS_OFFSET + 0x04;
+    const FR: usize = PL011_UART_REGS_OFFSET + 0x18;
+    const LCRH: usize = PL011_UART_REGS_OFFSET + 0x2C;
+    const CR: usize = PL011_UART_REGS_OFFSET + 0x30;
+    const MIS: usize = PL011_UART_REGS_OFFSET + 0x40;
+
+    #[test]
+    fn divisors_follow_the_uart_clock_and_unattainable_rates_are_rejected() {
+        assert_eq!(divisors(48_000_000, 115200), Ok((26, 3)));
+        assert_eq!(divisors(3_000_000, 9600), Ok((19, 34))); // Minimum firmware UARTCLK
+        assert_eq!(
+            divisors(48_000_000, 4_000_000),
+            Err(ConfigError::BaudRate { requested: 4_000_000, achieved: 3_000_000 })
+        );
+        assert_eq!(divisors(48_000_000, 3_000_000), Ok((1, 0)));
+    }
+
+    #[test]
+    fn instances_use_their_own_registers_and_pins() {
+        sim::reset();
+        let peripherals = unsafe { Peripherals::steal() };
+        let gps = Pl011::new(peripherals.uart3, &UartConfig::new(9600));
+        if board::current().soc != Soc::Bcm2711 {
+            assert!(matches!(gps, Err(InitError::Unavailable("uart3"))));
+            return;
+        }
+        let gps = gps.unwrap();
+        let motor = Pl011::new(peripherals.uart5, &UartConfig::default()).unwrap();
+        // TXD3/RXD3 are ALT4 on GPIO4/5
+        assert_eq!(sim::peek(GPFSEL0) >> 12 & 0b111_111, 0b011_011);
+        assert_eq!(pins::owner_of(13).unwrap().unwrap().owner, "uart5");
+        assert_eq!(sim::last_write(PL011_UART3_REGS_OFFSET + 0x24), Some(312)); // 9600 baud
+        assert_eq!(sim::last_write(PL011_UART5_REGS_OFFSET + 0x24), Some(26)); // 115200 baud
+        motor.write_byte(b'M');
+        assert_eq!(sim::writes(PL011_UART5_REGS_OFFSET), [b'M' as u32]);
+        assert_eq!(sim::writes(PL011_UART3_REGS_OFFSET), []);
+        assert_eq!(gps.config().baud, 9600);
+        let button = crate::drivers::gpio::GpioPin::new(12, "button");
+        assert!(matches!(button, Err(PinError::AlreadyClaimed { pin: 12, owner: "uart5" })));
+    }
+
+    #[test]
+    fn flow_control_claims_the_handshake_pins_and_enables_the_handshake() {
+        sim::reset();
+        let uart = Pl011::new(unsafe { Peripherals::steal() }.uart0, &UartConfig::default()).unwrap();
+        assert_eq!(pins::owner_of(16), Ok(None));
+        sim::poke(FR, 1 << 7); // TXFE, not busy
+        let modem = UartConfig { flow_control: FlowControl::RtsCts, ..UartConfig::default() };
+        uart.configure(&modem).unwrap();
+        // CTS0/RTS0 are ALT3 on GPIO16/17
+        assert_eq!(sim::peek(GPFSEL1) >> 18 & 0b111_111, 0b111_111);
+        assert_eq!(pins::owner_of(17).unwrap().unwrap().owner, "uart0");
+        assert_eq!(sim::last_write(CR), Some((1 << 15) | (1 << 14) | 0x301)); // CTSEN, RTSEN
+
+        // Manual RTS: only GPIO17 stays claimed, the handshake is off and RTS follows `set_rts`
+        uart.configure(&UartConfig { flow_control: FlowControl::ManualRts, ..modem }).unwrap();
+        assert_eq!(pins::owner_of(16), Ok(None));
+        assert_eq!(sim::last_write(CR), Some(0x301));
+        uart.set_rts(true);
+        assert_eq!(sim::last_write(CR), Some((1 << 11) | 0x301));
+
+        // Handshake pins owned by someone else: rejected, nothing changed
+        let _led = crate::drivers::gpio::GpioPin::new(16, "led").unwrap();
+        let writes = sim::writes(CR).len();
+        assert_eq!(
+            uart.configure(&modem),
+            Err(ConfigError::Pin(PinError::AlreadyClaimed { pin: 16, owner: "led" }))
+        );
+        assert_eq!(sim::writes(CR).len(), writes);
+    }
+
+    #[test]
+    fn receive_errors_are_reported_counted_and_cleared() {
+        sim::reset();
+        let uart = Pl011::new(unsafe { Peripherals::steal() }.uart0, &UartConfig::default()).unwrap();
+        sim::poke(FR, 0); // RX FIFO not empty
+        sim::poke(DR, (1 << 10) | (1 << 8)); // Break (with FE)
+        assert_eq!(uart.try_read_byte(), Some(Err(UartError::Break)));
+        assert_eq!(sim::writes(RSRECR).len(), 1);
+        sim::poke(DR, (1 << 9) | (1 << 11) | b'x' as u32); // Parity error, and bytes lost before
+        assert_eq!(uart.try_read_byte(), Some(Err(UartError::Parity)));
+        sim::poke(DR, b'A' as u32);
+        assert_eq!(uart.try_read_byte(), Some(Ok(b'A')));
+        assert_eq!(sim::writes(RSRECR).len(), 2); // Clean bytes leave RSRECR alone
+        assert_eq!(uart.error_counts(), ErrorCounts { framing: 0, parity: 1, breaks: 1, overruns: 1 });
+
+        sim::poke(DR, (1 << 8) | b'?' as u32); // Framing error
+        assert_eq!(uart.try_read_byte(), Some(Err(UartError::Framing)));
+        uart.reset_error_counts();
+        assert_eq!(uart.error_counts().total(), 0);
+    }
+
+    #[test]
+    fn damaged_and_lost_bytes_are_not_handed_out_as_data() {
+        sim::reset();
+        let uart = Pl011::new(unsafe { Peripherals::steal() }.uart0, &UartConfig::default()).unwrap();
+        uart.enable_interrupts();
+        sim::poke(FR, 0); // The RX FIFO never empties: every interrupt moves FIFO_DEPTH bytes
+        sim::poke(DR, (1 << 8) | b'?' as u32); // Framing error
+        sim::poke(MIS, 1 << 4);
+        Pl011::handle_interrupt();
+        sim::poke(DR, b'A' as u32);
+        while !uart.with_state(|state| state.rx.is_full()) {
+            Pl011::handle_interrupt();
+        }
+        Pl011::handle_interrupt(); // Nobody reads: these bytes are lost
+        assert_eq!(uart.read_byte(), Some(b'A')); // The damaged bytes are skipped
+        while uart.read_byte().is_some() {}
+
+        // The loss is reported ahead of the first byte stored after it
+        sim::poke(DR, b'B' as u32);
+        Pl011::handle_interrupt();
+        assert_eq!(uart.try_read_byte(), Some(Err(UartError::Overrun)));
+        assert_eq!(uart.try_read_byte(), Some(Ok(b'B')));
+        assert_eq!(uart.error_counts().framing, FIFO_DEPTH as u32);
+        assert!(uart.error_counts().overruns >= FIFO_DEPTH as u32);
+        sim::poke(FR, (1 << 7) | (1 << 4)); // TX FIFO and RX FIFO empty
+        uart.disable_interrupts();
+    }
+
+    static BREAKS: AtomicU32 = AtomicU32::new(0);
+
+    fn count_break() {
+        BREAKS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn breaks_are_sent_and_detected() {
+        sim::reset();
+        let uart = Pl011::new(unsafe { Peripherals::steal() }.uart0, &UartConfig::default()).unwrap();
+        sim::poke(FR, 1 << 7); // TXFE, not busy
+        uart.send_break(1000);
+        let lcrh = sim::writes(LCRH);
+        assert_eq!(lcrh[lcrh.len() - 2..], [0x71, 0x70]); // BRK set, then cleared (8N1 with FIFOs)
+
+        uart.enable_interrupts();
+        uart.set_break_handler(Some(count_break));
+        sim::poke(FR, 0); // RX FIFO not empty
+        sim::poke(DR, (1 << 10) | (1 << 8)); // A break arrives as a 0 byte with BE (and FE)
+        sim::poke(MIS, 1 << 6); // Receive timeout
+        Pl011::handle_interrupt();
+        assert_eq!(BREAKS.load(Ordering::Relaxed), 1); // Once per interrupt, not per byte
+        assert!(uart.break_received());
+        assert!(!uart.break_received());
+        assert_eq!(uart.try_read_byte(), Some(Err(UartError::Break)));
+        sim::poke(FR, (1 << 7) | (1 << 4)); // TX FIFO and RX FIFO empty
+        uart.disable_interrupts();
+    }
+}
@@ -0,0 +1,83 @@
+//! Fixed-size byte ring buffer
+//!
+//! Used by the interrupt-driven UART mode to queue bytes between the interrupt handler and the
+//! main program. It does no locking itself: callers share it inside a critical section
+//! (`interrupts::without_interrupts`), like the other global tables of the kernel.
+
+/// A FIFO of up to `N` bytes.
+pub struct RingBuffer<const N: usize> {
+    data: [u8; N],
+    /// Index of the oldest byte.
+    head: usize,
+    /// Number of bytes queued.
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> Self {
+        RingBuffer { data: [0; N], head: 0, len: 0 }
+    }
+
+    /// Append `byte`. Returns `false` (and drops the byte) if the buffer is full.
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.data[(self.head + self.len) % N] = byte;
+        self.len += 1;
+        true
+    }
+
+    /// Take the oldest byte.
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let byte = self.data[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(byte)
+    }
+
+    /// Queued bytes, oldest first (without taking them).
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..self.len).map(move |offset| self.data[(self.head + offset) % N])
+    }
+
+    /// Drop all queued bytes.
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_come_out_in_order_across_the_wrap_around() {
+        let mut ring = RingBuffer::<4>::new();
+        for byte in 1..=4 {
+            assert!(ring.push(byte));
+        }
+        assert!(!ring.push(5)); // Full
+        assert_eq!(ring.pop(), Some(1));
+        assert_eq!(ring.pop(), Some(2));
+        assert!(ring.push(6));
+        assert_eq!(ring.len(), 3);
+        assert_eq!([ring.pop(), ring.pop(), ring.pop(), ring.pop()], [Some(3), Some(4), Some(6), None]);
+    }
+}
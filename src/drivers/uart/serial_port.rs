@@ -0,0 +1,185 @@
+//! Common interface of the UART drivers
+//!
+//! [`SerialPort`] is implemented by every UART: [`Pl011`] (UART0, UART2-5), and the module-level
+//! drivers through [`Uart0Serial`] and [`MiniUartSerial`]. Code that only needs "a serial port"
+//! (the console, a protocol driver) takes a `&dyn SerialPort` and works with all of them.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::uart::{MiniUartSerial, SerialPort, Uart0Serial};
+//!
+//! fn greet(port: &dyn SerialPort) {
+//!     port.write_string("Hello from ");
+//!     port.write_string(port.name());
+//!     port.write_string("!\r\n");
+//! }
+//!
+//! greet(&Uart0Serial);
+//! greet(&MiniUartSerial);
+//! ```
+
+use super::config::{ConfigError, UartConfig};
+use super::error::{ErrorCounts, ReadError, UartError};
+use super::pl011::Pl011;
+use super::{mini_uart, uart0};
+use crate::drivers::timer::Deadline;
+
+/// An initialized UART.
+pub trait SerialPort {
+    /// Driver name, as used for the pin claims (e.g. "uart0").
+    fn name(&self) -> &'static str;
+    /// Send a byte, waiting for room in the TX FIFO (or queueing it in interrupt mode).
+    fn write_byte(&self, byte: u8);
+    /// Read a byte if one is available, dropping bytes received with an error.
+    fn read_byte(&self) -> Option<u8>;
+    /// Read a byte if one is available, or the error it was received with.
+    fn try_read_byte(&self) -> Option<Result<u8, UartError>>;
+    fn is_data_ready(&self) -> bool;
+    /// Wait until all pending output has been sent.
+    fn flush(&self);
+    /// Hold TX low for `duration_us` (at least two frames).
+    fn send_break(&self, duration_us: u32);
+    /// Apply new line settings at runtime.
+    fn configure(&self, config: &UartConfig) -> Result<(), ConfigError>;
+    /// Line settings currently in use.
+    fn config(&self) -> UartConfig;
+    /// Receive errors counted so far.
+    fn error_counts(&self) -> ErrorCounts;
+
+    fn write_string(&self, s: &str) {
+        for byte in s.bytes() {
+            self.write_byte(byte); // Send each byte
+        }
+    }
+
+    /// Wait up to `timeout_us` microseconds for a byte.
+    fn read_byte_timeout(&self, timeout_us: u32) -> Result<u8, ReadError> {
+        let mut byte = [0];
+        self.read_exact_timeout(&mut byte, timeout_us)?;
+        Ok(byte[0])
+    }
+
+    /// Fill `buffer`, giving up if it is not full after `timeout_us` microseconds (a deadline for
+    /// the whole buffer, not per byte) or when a byte arrives with an error.
+    fn read_exact_timeout(&self, buffer: &mut [u8], timeout_us: u32) -> Result<(), ReadError> {
+        let deadline = Deadline::after_us(timeout_us);
+        for slot in buffer.iter_mut() {
+            *slot = loop {
+                match self.try_read_byte() {
+                    Some(byte) => break byte?,
+                    None if deadline.has_passed() => return Err(ReadError::Timeout),
+                    None => core::hint::spin_loop(),
+                }
+            };
+        }
+        Ok(())
+    }
+}
+
+/// UART0 through the `uart0` module functions. Initialize it with `uart0::init` first.
+#[derive(Clone, Copy, Default)]
+pub struct Uart0Serial;
+
+/// The Mini UART through the `mini_uart` module functions. Initialize it with `mini_uart::init` first.
+#[derive(Clone, Copy, Default)]
+pub struct MiniUartSerial;
+
+/// Implement `SerialPort` for a serial port type on top of a UART driver module.
+macro_rules! impl_serial_port {
+    ($serial:ty, $uart:ident, $name:literal) => {
+        impl SerialPort for $serial {
+            fn name(&self) -> &'static str {
+                $name
+            }
+
+            fn write_byte(&self, byte: u8) {
+                $uart::write_byte(byte);
+            }
+
+            fn read_byte(&self) -> Option<u8> {
+                $uart::read_byte()
+            }
+
+            fn try_read_byte(&self) -> Option<Result<u8, UartError>> {
+                $uart::try_read_byte()
+            }
+
+            fn is_data_ready(&self) -> bool {
+                $uart::is_data_ready()
+            }
+
+            fn flush(&self) {
+                $uart::flush();
+            }
+
+            fn send_break(&self, duration_us: u32) {
+                $uart::send_break(duration_us);
+            }
+
+            fn configure(&self, config: &UartConfig) -> Result<(), ConfigError> {
+                $uart::configure(config)
+            }
+
+            fn config(&self) -> UartConfig {
+                $uart::config()
+            }
+
+            fn error_counts(&self) -> ErrorCounts {
+                $uart::error_counts()
+            }
+
+            fn write_string(&self, s: &str) {
+                $uart::write_string(s);
+            }
+        }
+    };
+}
+
+impl_serial_port!(Uart0Serial, uart0, "uart0");
+impl_serial_port!(MiniUartSerial, mini_uart, "mini_uart");
+
+impl SerialPort for Pl011 {
+    fn name(&self) -> &'static str {
+        Pl011::name(self)
+    }
+
+    fn write_byte(&self, byte: u8) {
+        Pl011::write_byte(self, byte);
+    }
+
+    fn read_byte(&self) -> Option<u8> {
+        Pl011::read_byte(self)
+    }
+
+    fn try_read_byte(&self) -> Option<Result<u8, UartError>> {
+        Pl011::try_read_byte(self)
+    }
+
+    fn is_data_ready(&self) -> bool {
+        Pl011::is_data_ready(self)
+    }
+
+    fn flush(&self) {
+        Pl011::flush(self);
+    }
+
+    fn send_break(&self, duration_us: u32) {
+        Pl011::send_break(self, duration_us);
+    }
+
+    fn configure(&self, config: &UartConfig) -> Result<(), ConfigError> {
+        Pl011::configure(self, config)
+    }
+
+    fn config(&self) -> UartConfig {
+        Pl011::config(self)
+    }
+
+    fn error_counts(&self) -> ErrorCounts {
+        Pl011::error_counts(self)
+    }
+
+    fn write_string(&self, s: &str) {
+        Pl011::write_string(self, s);
+    }
+}
@@ -1,103 +1,252 @@
 //! PL011 UART (UART0) driver for Raspberry Pi
 //!
 //! Provides blocking read/write and initialization routines for the full UART0 peripheral.
+//! These are module-level functions for UART0, the console UART, on top of the multi-instance
+//! [`Pl011`] driver; use a `Pl011` directly for UART2-5.
+//!
+//! After [`enable_interrupts`], UART0 runs interrupt-driven: received bytes are moved from the
+//! hardware FIFO into a software RX ring buffer by the UART interrupt, so none are lost while the
+//! program is busy, and `write_byte` queues into a TX ring buffer instead of waiting for the FIFO.
+//! The functions below keep their signatures in both modes.
 //!
 //! # Example
 //! ```rust
 //! use crate::drivers::uart::uart0;
 //!
 //! fn main() {
-//!     uart0::init();
+//!     let peripherals = Peripherals::take().unwrap();
+//!     uart0::init(peripherals.uart0, &UartConfig::default()).unwrap(); // Claims GPIO14/15, 115200 8N1
 //!     uart0::write_string("Hello from UART0!\r\n");
 //!     if let Some(byte) = uart0::read_byte() {
 //!         uart0::write_byte(byte);
 //!     }
+//!
+//!     uart0::configure(&UartConfig::new(921600)).unwrap(); // Change the baud rate at runtime
+//!
+//!     interrupts::init();
+//!     uart0::enable_interrupts(); // From now on, RX/TX go through the ring buffers
+//!     interrupts::enable();
 //! }
 //! ```
 
-use crate::hal::registers::uart::PL011_UART_REGS;
-use crate::hal::registers::gpio::GPIO_REGS;
-
-pub fn init() {
-    unsafe {
-        let gpio_regs = &mut *GPIO_REGS;
-        gpio_regs.gpfsel1 &= !(0b111 << 12); // Clear FSEL14 (TX)
-        gpio_regs.gpfsel1 &= !(0b111 << 15); // Clear FSEL15 (RX)
-        gpio_regs.gpfsel1 |= 0b100 << 12; // Set GPIO14 to ALT0 (UART0 TX)
-        gpio_regs.gpfsel1 |= 0b100 << 15; // Set GPIO15 to ALT0 (UART0 RX)
-        // Optionally: disable pull-up/down for pins 14/15 here if needed
-        let uart = &mut *PL011_UART_REGS;
-        uart.cr = 0; // Disable UART0 before config
-        uart.icr = 0x7FF; // Clear all pending interrupts
-        uart.ibrd = 26; // Set integer baud rate divisor (for 115200 baud @ 48MHz)
-        uart.fbrd = 3;  // Set fractional baud rate divisor
-        uart.lcrh = (1 << 4) | (1 << 5) | (1 << 6); // 8N1, enable FIFOs
-        uart.cr = (1 << 0) | (1 << 8) | (1 << 9); // Enable UART, TX, RX
-    }
+use crate::drivers::peripherals::Uart0;
+use crate::interrupts::Handler;
+
+use super::config::{ConfigError, InitError, UartConfig};
+use super::error::{ErrorCounts, ReadError, UartError};
+use super::line_editor::LineError;
+use super::pl011::Pl011;
+
+/// Initialize UART0 on GPIO14 (TX) / GPIO15 (RX) with the given line settings.
+/// Fails if another driver owns one of the pins or the baud rate cannot be generated.
+pub fn init(uart: Uart0, config: &UartConfig) -> Result<(), InitError> {
+    Pl011::new(uart, config).map(|_| ())
+}
+
+/// Apply new line settings at runtime. Pending output is sent with the old settings first.
+/// On error nothing is changed.
+pub fn configure(config: &UartConfig) -> Result<(), ConfigError> {
+    Pl011::UART0.configure(config)
+}
+
+/// Line settings currently in use.
+pub fn config() -> UartConfig {
+    Pl011::UART0.config()
+}
+
+/// Switch to interrupt-driven mode: RX/TX through ring buffers, moved by the UART interrupt.
+/// Call after `init` and `interrupts::init`; the bytes only move once IRQs are unmasked.
+pub fn enable_interrupts() {
+    Pl011::UART0.enable_interrupts();
+}
+
+/// Go back to polled mode. Queued TX bytes are sent first; unread RX bytes are dropped.
+pub fn disable_interrupts() {
+    Pl011::UART0.disable_interrupts();
+}
+
+/// Check whether UART0 runs interrupt-driven.
+pub fn interrupts_enabled() -> bool {
+    Pl011::UART0.interrupts_enabled()
 }
 
 pub fn write_byte(byte: u8) {
-    unsafe {
-        let uart = &mut *PL011_UART_REGS;
-        while (uart.fr & (1 << 5)) != 0 {} // Wait for TX FIFO to have space
-        uart.dr = byte as u32; // Write byte to TX FIFO
-    }
+    Pl011::UART0.write_byte(byte);
 }
 
 pub fn write_string(s: &str) {
-    for byte in s.bytes() {
-        write_byte(byte); // Send each byte
-    }
+    Pl011::UART0.write_string(s);
 }
 
+/// Read a byte if one is available. Bytes received with an error are dropped (and counted).
 pub fn read_byte() -> Option<u8> {
-    unsafe {
-        let uart = &*PL011_UART_REGS;
-        if (uart.fr & (1 << 4)) == 0 { // RX FIFO not empty?
-            Some((uart.dr & 0xFF) as u8) // Read byte
-        } else {
-            None // No data available
-        }
-    }
+    Pl011::UART0.read_byte()
+}
+
+/// Read a byte if one is available, or the error it was received with.
+pub fn try_read_byte() -> Option<Result<u8, UartError>> {
+    Pl011::UART0.try_read_byte()
+}
+
+/// Receive errors counted since `init` (or `reset_error_counts`).
+pub fn error_counts() -> ErrorCounts {
+    Pl011::UART0.error_counts()
+}
+
+pub fn reset_error_counts() {
+    Pl011::UART0.reset_error_counts();
+}
+
+/// Hold TX low for `duration_us` (at least two frames).
+pub fn send_break(duration_us: u32) {
+    Pl011::UART0.send_break(duration_us);
+}
+
+/// Check whether a break was received since the last call.
+pub fn break_received() -> bool {
+    Pl011::UART0.break_received()
+}
+
+/// Run `handler` from the UART interrupt when a break arrives (interrupt mode only).
+pub fn set_break_handler(handler: Option<Handler>) {
+    Pl011::UART0.set_break_handler(handler);
 }
 
 pub fn flush() {
-    unsafe {
-        let uart = &*PL011_UART_REGS;
-        while (uart.fr & (1 << 7)) == 0 {} // Wait for TX FIFO to be empty
-    }
+    Pl011::UART0.flush();
 }
 
-pub fn read_line(buffer: &mut [u8]) -> Option<usize> {
-    let mut pos = 0;
-    while pos < buffer.len() - 1 {
-        if let Some(byte) = read_byte() {
-            match byte {
-                b'\r' | b'\n' => { // End of line
-                    buffer[pos] = 0; // Null terminate
-                    return Some(pos);
-                }
-                b'\x08' | b'\x7f' => { // Backspace or DEL
-                    if pos > 0 {
-                        pos -= 1;
-                        write_string("\x08 \x08"); // Erase last char on terminal
-                    }
-                }
-                _ => {
-                    buffer[pos] = byte; // Store byte
-                    write_byte(byte);   // Echo back
-                    pos += 1;
-                }
-            }
-        }
-    }
-    buffer[pos] = 0; // Null terminate
-    Some(pos)
+pub fn read_line(buffer: &mut [u8]) -> Result<&str, LineError> {
+    Pl011::UART0.read_line(buffer)
+}
+
+/// Like `read_line`, but give up after `timeout_us` microseconds without Enter.
+pub fn read_line_timeout(buffer: &mut [u8], timeout_us: u32) -> Result<&str, LineError> {
+    Pl011::UART0.read_line_timeout(buffer, timeout_us)
+}
+
+/// Wait up to `timeout_us` microseconds for a byte.
+pub fn read_byte_timeout(timeout_us: u32) -> Result<u8, ReadError> {
+    Pl011::UART0.read_byte_timeout(timeout_us)
+}
+
+/// Fill `buffer` within `timeout_us` microseconds (see `SerialPort::read_exact_timeout`).
+pub fn read_exact_timeout(buffer: &mut [u8], timeout_us: u32) -> Result<(), ReadError> {
+    Pl011::UART0.read_exact_timeout(buffer, timeout_us)
 }
 
 pub fn is_data_ready() -> bool {
-    unsafe {
-        let uart = &*PL011_UART_REGS;
-        (uart.fr & (1 << 4)) == 0 // RX FIFO has data?
+    Pl011::UART0.is_data_ready()
+}
+
+/// Assert or deassert RTS (GPIO17) with `FlowControl::ManualRts`.
+pub fn set_rts(asserted: bool) {
+    Pl011::UART0.set_rts(asserted);
+}
+
+/// Check whether CTS (GPIO16) is asserted.
+pub fn is_cts_asserted() -> bool {
+    Pl011::UART0.is_cts_asserted()
+}
+
+/// UART interrupt handler of all PL011 instances (registered by `enable_interrupts`).
+pub fn handle_interrupt() {
+    Pl011::handle_interrupt();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::peripherals::Peripherals;
+    use crate::drivers::uart::{DataBits, Parity, StopBits};
+    use crate::interrupts::{self, irq};
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::gpio::GPIO_REGS_OFFSET;
+    use crate::hal::registers::uart::PL011_UART_REGS_OFFSET;
+
+    const GPFSEL1: usize = GPIO_REGS_OFFSET + 0x04;
+    const DR: usize = PL011_UART_REGS_OFFSET;
+    const FR: usize = PL011_UART_REGS_OFFSET + 0x18;
+    const IBRD: usize = PL011_UART_REGS_OFFSET + 0x24;
+    const FBRD: usize = PL011_UART_REGS_OFFSET + 0x28;
+    const LCRH: usize = PL011_UART_REGS_OFFSET + 0x2C;
+    const CR: usize = PL011_UART_REGS_OFFSET + 0x30;
+    const IMSC: usize = PL011_UART_REGS_OFFSET + 0x38;
+    const MIS: usize = PL011_UART_REGS_OFFSET + 0x40;
+
+    #[test]
+    fn init_selects_alt0_and_115200_baud() {
+        sim::reset();
+        init(unsafe { Peripherals::steal() }.uart0, &UartConfig::default()).unwrap();
+        assert_eq!(sim::peek(GPFSEL1), (0b100 << 12) | (0b100 << 15));
+        // 48 MHz / (16 * 115200) = 26.04 -> IBRD 26, FBRD round(0.04 * 64) = 3
+        assert_eq!(sim::last_write(IBRD), Some(26));
+        assert_eq!(sim::last_write(FBRD), Some(3));
+        // Disabled during configuration, enabled last
+        assert_eq!(sim::writes(CR), [0, 0x301]);
+    }
+
+    #[test]
+    fn configure_reapplies_the_frame_format_with_the_uart_disabled() {
+        sim::reset();
+        sim::poke(FR, 1 << 7); // TXFE, not busy
+        sim::poke(CR, 0x301);
+        let config = UartConfig {
+            data_bits: DataBits::Seven,
+            parity: Parity::Odd,
+            stop_bits: StopBits::Two,
+            ..UartConfig::new(9600)
+        };
+        configure(&config).unwrap();
+        assert_eq!(sim::last_write(IBRD), Some(312)); // 48 MHz / (16 * 9600) = 312.5
+        assert_eq!(sim::last_write(FBRD), Some(32));
+        assert_eq!(sim::last_write(LCRH), Some((0b10 << 5) | (1 << 4) | (1 << 3) | (1 << 1)));
+        assert_eq!(sim::writes(CR), [0x300, 0x301]);
+        assert_eq!(super::config(), config);
+        assert!(configure(&UartConfig::new(5_000_000)).is_err());
+        assert_eq!(sim::writes(CR).len(), 2); // Rejected before touching the UART
+    }
+
+    #[test]
+    fn read_byte_only_reads_when_the_rx_fifo_is_not_empty() {
+        sim::reset();
+        sim::poke(FR, 1 << 4); // RXFE
+        assert_eq!(read_byte(), None);
+        sim::poke(FR, 0);
+        sim::poke(DR, 0x441); // Break flag + 'A'
+        assert_eq!(try_read_byte(), Some(Err(UartError::Break)));
+        sim::poke(DR, b'A' as u32);
+        assert_eq!(read_byte(), Some(b'A'));
+    }
+
+    #[test]
+    fn interrupt_mode_buffers_rx_and_queues_tx_behind_a_full_fifo() {
+        sim::reset();
+        enable_interrupts();
+        assert_eq!(sim::peek(IMSC), (1 << 4) | (1 << 6)); // RX + receive timeout
+        assert!(interrupts::is_registered(irq::UART));
+
+        // Received bytes are moved into the RX buffer by the interrupt, one FIFO's worth at most
+        sim::poke(FR, 0);
+        sim::poke(DR, b'A' as u32);
+        sim::poke(MIS, 1 << 4);
+        handle_interrupt();
+        assert_eq!(read_byte(), Some(b'A'));
+
+        // TX FIFO full: the byte is queued and the TX interrupt unmasked
+        sim::poke(FR, (1 << 5) | (1 << 4)); // TXFF, RXFE
+        let before = sim::writes(DR).len();
+        write_byte(b'x');
+        assert_eq!(sim::writes(DR).len(), before);
+        assert_eq!(sim::peek(IMSC) & (1 << 5), 1 << 5);
+        // FIFO drained: the interrupt sends it and masks TX again
+        sim::poke(FR, 1 << 4);
+        sim::poke(MIS, 1 << 5);
+        handle_interrupt();
+        assert_eq!(sim::last_write(DR), Some(b'x' as u32));
+        assert_eq!(sim::peek(IMSC) & (1 << 5), 0);
+        sim::poke(FR, (1 << 7) | (1 << 4)); // TXFE: the flush in `disable_interrupts` returns
+        disable_interrupts();
+        assert!(!interrupts_enabled());
     }
 }
@@ -0,0 +1,28 @@
+//! Watchdog reset
+//!
+//! The PM watchdog resets the whole SoC when it expires. We only use it to reboot: start it with a
+//! short timeout and wait. The firmware then boots the kernel again from the SD card.
+//!
+//! # Example
+//! ```rust
+//! use crate::drivers::watchdog;
+//!
+//! watchdog::reboot();
+//! ```
+
+use crate::hal::registers::power::{pm_regs, PM_RSTC, PM_WDOG};
+
+/// Watchdog ticks before the reset (about 150 us), enough for the UART to finish a last line.
+const REBOOT_TICKS: u32 = 10;
+
+/// Reset the SoC through the watchdog.
+pub fn reboot() -> ! {
+    let regs = pm_regs();
+    regs.wdog.write(PM_WDOG::PASSWD::Password + PM_WDOG::TIME.val(REBOOT_TICKS));
+    // Keep the other reset settings, select a full reset
+    let rstc = (PM_RSTC::PASSWD::Password + PM_RSTC::WRCFG::FullReset).modify(regs.rstc.get());
+    regs.rstc.set(rstc);
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,215 @@
+//! MMIO backend.
+//!
+//! Every register access made through the cells in `hal::registers::access` ends up in one of the
+//! two functions of [`MmioBackend`]. Which implementation is used is decided at compile time:
+//! - [`Hardware`] on the target: plain volatile loads and stores to the peripheral window.
+//! - [`sim::Simulated`] in host unit tests (`cfg(test)`): a simulated register file that records
+//!   every access, so driver logic can be tested with `cargo test` on a development machine.
+//!
+//! # Example (host test)
+//! ```rust
+//! use crate::hal::mmio::sim;
+//!
+//! sim::reset();
+//! GpioPin::new(42, "test")?.set_output();
+//! assert_eq!(sim::last_write(GPIO_REGS_OFFSET + 0x10), Some(0b001 << 6));
+//! ```
+
+/// Low level access to memory-mapped registers.
+pub trait MmioBackend {
+    /// Base address of the peripheral window that the register blocks are located in.
+    fn peripheral_base() -> usize;
+
+    /// Read the 32-bit register at `address`.
+    ///
+    /// # Safety
+    /// `address` must be a 4-byte aligned register inside the peripheral window.
+    unsafe fn read32(address: usize) -> u32;
+
+    /// Write `value` to the 32-bit register at `address`.
+    ///
+    /// # Safety
+    /// `address` must be a 4-byte aligned register inside the peripheral window.
+    unsafe fn write32(address: usize, value: u32);
+}
+
+/// The real hardware: volatile accesses to the peripheral window of the current board.
+pub struct Hardware;
+
+impl MmioBackend for Hardware {
+    #[inline]
+    fn peripheral_base() -> usize {
+        crate::board::current().peripheral_base
+    }
+
+    #[inline]
+    unsafe fn read32(address: usize) -> u32 {
+        core::ptr::read_volatile(address as *const u32)
+    }
+
+    #[inline]
+    unsafe fn write32(address: usize, value: u32) {
+        core::ptr::write_volatile(address as *mut u32, value)
+    }
+}
+
+/// The backend used by this build.
+#[cfg(not(test))]
+pub type Backend = Hardware;
+
+/// The backend used by this build.
+#[cfg(test)]
+pub type Backend = sim::Simulated;
+
+/// Base address of the peripheral window of the active backend.
+#[inline]
+pub fn peripheral_base() -> usize {
+    Backend::peripheral_base()
+}
+
+/// Simulated register file for host unit tests.
+///
+/// Each test thread gets its own zero-initialized copy of the 32 MB peripheral window, so tests
+/// running in parallel do not see each other's accesses. Registers behave like plain memory:
+/// a write is read back unchanged, and tests can preset status registers with [`poke`] (or queue
+/// the successive words of a FIFO with [`queue_reads`]).
+/// All offsets are relative to the peripheral base (e.g. `GPIO_REGS_OFFSET + 0x10` for GPFSEL4).
+#[cfg(test)]
+pub mod sim {
+    extern crate std;
+
+    use super::MmioBackend;
+    use std::cell::RefCell;
+    use std::vec;
+    use std::vec::Vec;
+
+    /// Size of the simulated peripheral window.
+    const WINDOW_SIZE: usize = 0x0200_0000;
+
+    /// One recorded register access.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum Access {
+        Read { offset: usize, value: u32 },
+        Write { offset: usize, value: u32 },
+    }
+
+    struct Simulator {
+        /// Backing memory of the window. Leaked so the register blocks can hand out `&'static`s,
+        /// and only accessed through this raw pointer.
+        window: *mut u32,
+        trace: Vec<Access>,
+        /// Words written since the last reset (so `reset` does not have to clear 16 MB).
+        dirty: Vec<usize>,
+        /// Values returned by the next reads of a register, oldest first (see `queue_reads`).
+        queued: Vec<(usize, u32)>,
+    }
+
+    std::thread_local! {
+        static SIMULATOR: RefCell<Simulator> = RefCell::new(Simulator {
+            window: vec![0u32; WINDOW_SIZE / 4].leak().as_mut_ptr(),
+            trace: Vec::new(),
+            dirty: Vec::new(),
+            queued: Vec::new(),
+        });
+    }
+
+    /// The simulated backend (see the module documentation).
+    pub struct Simulated;
+
+    impl MmioBackend for Simulated {
+        fn peripheral_base() -> usize {
+            SIMULATOR.with(|sim| sim.borrow().window as usize)
+        }
+
+        unsafe fn read32(address: usize) -> u32 {
+            SIMULATOR.with(|sim| {
+                let sim = &mut *sim.borrow_mut();
+                let offset = offset_of(sim, address);
+                let value = match sim.queued.iter().position(|&(queued, _)| queued == offset) {
+                    Some(index) => sim.queued.remove(index).1,
+                    None => sim.window.add(offset / 4).read_volatile(),
+                };
+                sim.trace.push(Access::Read { offset, value });
+                value
+            })
+        }
+
+        unsafe fn write32(address: usize, value: u32) {
+            SIMULATOR.with(|sim| {
+                let sim = &mut *sim.borrow_mut();
+                let offset = offset_of(sim, address);
+                sim.window.add(offset / 4).write_volatile(value);
+                sim.dirty.push(offset);
+                sim.trace.push(Access::Write { offset, value });
+            })
+        }
+    }
+
+    fn offset_of(sim: &Simulator, address: usize) -> usize {
+        let offset = address.wrapping_sub(sim.window as usize);
+        assert!(offset < WINDOW_SIZE && offset.is_multiple_of(4), "access outside the peripheral window: {:#x}", address);
+        offset
+    }
+
+    /// Clear all registers and the trace. Call this at the start of every test.
+    pub fn reset() {
+        SIMULATOR.with(|sim| {
+            let sim = &mut *sim.borrow_mut();
+            for offset in sim.dirty.drain(..) {
+                unsafe { sim.window.add(offset / 4).write_volatile(0) };
+            }
+            sim.trace.clear();
+            sim.queued.clear();
+        });
+    }
+
+    /// Set a register without recording an access (e.g. to preset a status register).
+    pub fn poke(offset: usize, value: u32) {
+        SIMULATOR.with(|sim| {
+            let sim = &mut *sim.borrow_mut();
+            let offset = offset_of(sim, sim.window as usize + offset);
+            unsafe { sim.window.add(offset / 4).write_volatile(value) };
+            sim.dirty.push(offset);
+        });
+    }
+
+    /// Make the next reads of the register at `offset` return `values`, in order, before it reads
+    /// as plain memory again (e.g. the words arriving in a receive FIFO).
+    pub fn queue_reads(offset: usize, values: &[u32]) {
+        SIMULATOR.with(|sim| {
+            let sim = &mut *sim.borrow_mut();
+            let offset = offset_of(sim, sim.window as usize + offset);
+            sim.queued.extend(values.iter().map(|&value| (offset, value)));
+        });
+    }
+
+    /// Current value of a register, without recording an access.
+    pub fn peek(offset: usize) -> u32 {
+        SIMULATOR.with(|sim| {
+            let sim = sim.borrow();
+            let offset = offset_of(&sim, sim.window as usize + offset);
+            unsafe { sim.window.add(offset / 4).read_volatile() }
+        })
+    }
+
+    /// All accesses recorded since the last [`reset`], in order.
+    pub fn trace() -> Vec<Access> {
+        SIMULATOR.with(|sim| sim.borrow().trace.clone())
+    }
+
+    /// All values written to the register at `offset` since the last [`reset`], in order.
+    pub fn writes(offset: usize) -> Vec<u32> {
+        trace()
+            .into_iter()
+            .filter_map(|access| match access {
+                Access::Write { offset: o, value } if o == offset => Some(value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The last value written to the register at `offset`, if any.
+    pub fn last_write(offset: usize) -> Option<u32> {
+        writes(offset).last().copied()
+    }
+}
@@ -1 +1,2 @@
+pub mod mmio;
 pub mod registers;
\ No newline at end of file
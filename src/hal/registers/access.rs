@@ -0,0 +1,294 @@
+//! Typed volatile register cells and bitfields.
+//!
+//! Every memory-mapped register in `hal::registers` is declared as one of three cell types:
+//! - [`ReadOnly`]  - status registers such as `FR` or `AUX_MU_LSR_REG` (no `set`/`write`/`modify`)
+//! - [`WriteOnly`] - strobe registers such as `GPSETn` or `ICR` (no `get`/`read`)
+//! - [`ReadWrite`] - ordinary control registers
+//!
+//! All accesses go through the MMIO backend (`hal::mmio`): volatile loads and stores on the target,
+//! so the compiler can never merge, reorder or drop them, and a simulated register file in host tests.
+//! Using the wrong access (e.g. writing a read-only register) is a compile error.
+//!
+//! Bitfields are declared with the [`register_bitfields!`](crate::register_bitfields) macro, which
+//! generates one module per register containing a `Field` constant per bitfield and the named
+//! values of that field. Fields are tied to their register through a marker type, so a field of
+//! `LCRH` cannot accidentally be written into `CR`.
+//!
+//! # Example
+//! ```rust
+//! register_bitfields! {
+//!     LCRH [
+//!         FEN OFFSET(4) NUMBITS(1) [],
+//!         WLEN OFFSET(5) NUMBITS(2) [
+//!             Five = 0b00,
+//!             Eight = 0b11
+//!         ]
+//!     ]
+//! }
+//!
+//! uart.lcrh.write(LCRH::FEN::SET + LCRH::WLEN::Eight); // Plain write, other bits zero
+//! uart.lcrh.modify(LCRH::FEN::CLEAR);                  // Read-modify-write of one field
+//! if uart.fr.is_set(FR::TXFF) { /* TX FIFO full */ }
+//! ```
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::ops::Add;
+
+use crate::hal::mmio::{Backend, MmioBackend};
+
+/// A bitfield inside a register of type `R`: a right-aligned `mask` and its `shift`.
+pub struct Field<R> {
+    pub mask: u32,
+    pub shift: u32,
+    associated_register: PhantomData<R>,
+}
+
+// Implemented by hand so `R` (a marker type) does not need to be `Copy` itself.
+impl<R> Clone for Field<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<R> Copy for Field<R> {}
+
+impl<R> Field<R> {
+    /// Create a field covering `mask << shift`. `mask` must be right-aligned (e.g. `0b111`).
+    pub const fn new(mask: u32, shift: u32) -> Self {
+        Field { mask, shift, associated_register: PhantomData }
+    }
+
+    /// Extract this field from a raw register value.
+    pub const fn read(self, register: u32) -> u32 {
+        (register >> self.shift) & self.mask
+    }
+
+    /// Check whether any bit of this field is set in a raw register value.
+    pub const fn is_set(self, register: u32) -> bool {
+        self.read(register) != 0
+    }
+
+    /// Build a field value from a raw number. Bits outside the field width are dropped.
+    pub const fn val(self, value: u32) -> FieldValue<R> {
+        FieldValue::new(self.mask, self.shift, value)
+    }
+}
+
+/// A value for one or more fields of a register of type `R`.
+///
+/// Field values can be combined with `+` to update several fields in a single access:
+/// `LCRH::FEN::SET + LCRH::WLEN::Eight`.
+pub struct FieldValue<R> {
+    /// Bits of the register covered by this value (already shifted into place).
+    pub mask: u32,
+    /// The value of those bits (already shifted into place).
+    pub value: u32,
+    associated_register: PhantomData<R>,
+}
+
+impl<R> Clone for FieldValue<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<R> Copy for FieldValue<R> {}
+
+impl<R> FieldValue<R> {
+    /// Create a field value from a right-aligned `mask`, its `shift` and a right-aligned `value`.
+    pub const fn new(mask: u32, shift: u32, value: u32) -> Self {
+        FieldValue {
+            mask: mask << shift,
+            value: (value & mask) << shift,
+            associated_register: PhantomData,
+        }
+    }
+
+    /// Apply this value to a raw register value, leaving bits outside the mask untouched.
+    pub const fn modify(self, register: u32) -> u32 {
+        (register & !self.mask) | self.value
+    }
+
+    /// Check whether a raw register value holds exactly this value in the masked bits.
+    pub const fn matches(self, register: u32) -> bool {
+        register & self.mask == self.value
+    }
+}
+
+impl<R> Add for FieldValue<R> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        FieldValue {
+            mask: self.mask | rhs.mask,
+            value: self.value | rhs.value,
+            associated_register: PhantomData,
+        }
+    }
+}
+
+/// Read-only register. Writing to it does not compile.
+#[repr(transparent)]
+pub struct ReadOnly<R = ()> {
+    value: UnsafeCell<u32>,
+    associated_register: PhantomData<R>,
+}
+
+/// Write-only register. Reading from it does not compile.
+#[repr(transparent)]
+pub struct WriteOnly<R = ()> {
+    value: UnsafeCell<u32>,
+    associated_register: PhantomData<R>,
+}
+
+/// Read-write register.
+#[repr(transparent)]
+pub struct ReadWrite<R = ()> {
+    value: UnsafeCell<u32>,
+    associated_register: PhantomData<R>,
+}
+
+impl<R> ReadOnly<R> {
+    /// Volatile read of the raw register value.
+    #[inline]
+    pub fn get(&self) -> u32 {
+        unsafe { Backend::read32(self.value.get() as usize) }
+    }
+
+    /// Read a single field.
+    #[inline]
+    pub fn read(&self, field: Field<R>) -> u32 {
+        field.read(self.get())
+    }
+
+    /// Check whether any bit of `field` is set.
+    #[inline]
+    pub fn is_set(&self, field: Field<R>) -> bool {
+        field.is_set(self.get())
+    }
+
+    /// Check whether all fields in `value` currently hold the given values.
+    #[inline]
+    pub fn matches_all(&self, value: FieldValue<R>) -> bool {
+        value.matches(self.get())
+    }
+}
+
+impl<R> WriteOnly<R> {
+    /// Volatile write of a raw register value.
+    #[inline]
+    pub fn set(&self, value: u32) {
+        unsafe { Backend::write32(self.value.get() as usize, value) }
+    }
+
+    /// Write the given field values. All other bits are written as zero.
+    #[inline]
+    pub fn write(&self, value: FieldValue<R>) {
+        self.set(value.value);
+    }
+}
+
+impl<R> ReadWrite<R> {
+    /// Volatile read of the raw register value.
+    #[inline]
+    pub fn get(&self) -> u32 {
+        unsafe { Backend::read32(self.value.get() as usize) }
+    }
+
+    /// Volatile write of a raw register value.
+    #[inline]
+    pub fn set(&self, value: u32) {
+        unsafe { Backend::write32(self.value.get() as usize, value) }
+    }
+
+    /// Read a single field.
+    #[inline]
+    pub fn read(&self, field: Field<R>) -> u32 {
+        field.read(self.get())
+    }
+
+    /// Check whether any bit of `field` is set.
+    #[inline]
+    pub fn is_set(&self, field: Field<R>) -> bool {
+        field.is_set(self.get())
+    }
+
+    /// Check whether all fields in `value` currently hold the given values.
+    #[inline]
+    pub fn matches_all(&self, value: FieldValue<R>) -> bool {
+        value.matches(self.get())
+    }
+
+    /// Write the given field values. All other bits are written as zero.
+    #[inline]
+    pub fn write(&self, value: FieldValue<R>) {
+        self.set(value.value);
+    }
+
+    /// Read-modify-write: update the given fields and keep all other bits as they are.
+    /// This is one volatile read followed by one volatile write (not atomic).
+    #[inline]
+    pub fn modify(&self, value: FieldValue<R>) {
+        self.set(value.modify(self.get()));
+    }
+}
+
+/// Declare the bitfields of one or more registers.
+///
+/// For every register `REG` this generates a module `REG` containing:
+/// - `REG::Register`: marker type used as the `R` parameter of the register cell
+/// - `REG::FIELD`: a [`Field`] constant for every field
+/// - `REG::FIELD::SET` / `REG::FIELD::CLEAR`: all bits of the field set / cleared
+/// - `REG::FIELD::Name`: one [`FieldValue`] constant per named value
+#[macro_export]
+macro_rules! register_bitfields {
+    (
+        $(
+            $(#[$reg_attr:meta])*
+            $reg:ident [
+                $(
+                    $(#[$field_attr:meta])*
+                    $field:ident OFFSET($offset:expr) NUMBITS($numbits:expr) [
+                        $( $(#[$value_attr:meta])* $value_name:ident = $value:expr ),* $(,)?
+                    ]
+                ),* $(,)?
+            ]
+        ),* $(,)?
+    ) => {
+        $(
+            $(#[$reg_attr])*
+            #[allow(non_snake_case, dead_code)]
+            pub mod $reg {
+                /// Marker type tying the fields below to this register.
+                pub struct Register;
+
+                $(
+                    $(#[$field_attr])*
+                    #[allow(non_upper_case_globals)]
+                    pub const $field: $crate::hal::registers::access::Field<Register> =
+                        $crate::hal::registers::access::Field::new(
+                            ((1u64 << $numbits) - 1) as u32,
+                            $offset,
+                        );
+
+                    #[allow(non_snake_case, non_upper_case_globals, dead_code)]
+                    pub mod $field {
+                        use super::Register;
+                        use $crate::hal::registers::access::FieldValue;
+
+                        const MASK: u32 = ((1u64 << $numbits) - 1) as u32;
+
+                        /// All bits of this field set.
+                        pub const SET: FieldValue<Register> = FieldValue::new(MASK, $offset, MASK);
+                        /// All bits of this field cleared.
+                        pub const CLEAR: FieldValue<Register> = FieldValue::new(MASK, $offset, 0);
+                        $(
+                            $(#[$value_attr])*
+                            pub const $value_name: FieldValue<Register> =
+                                FieldValue::new(MASK, $offset, $value);
+                        )*
+                    }
+                )*
+            }
+        )*
+    };
+}
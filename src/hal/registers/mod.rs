@@ -1,6 +1,8 @@
 // Create to define Register definitions and accessors for the rasp4b peripherals.
+//
+// The register modules (`gpio`, `auxiliary`, `uart`, `interrupt`, ...) are generated by `build.rs`
+// from `peripherals.regmap`: to add a peripheral, describe its registers there.
 
-pub mod utils;
-pub mod gpio;
-pub mod auxiliary;
-pub mod uart;
\ No newline at end of file
+pub mod access;
+
+include!(concat!(env!("OUT_DIR"), "/registers.rs"));
@@ -0,0 +1,68 @@
+//! GIC-400 interrupt controller (BCM2711).
+//!
+//! The VideoCore peripheral interrupts are shared peripheral interrupts (SPIs) starting at
+//! interrupt ID 96. All of them are routed to core 0 with the same priority, level-triggered.
+
+use super::IRQ_COUNT;
+use crate::hal::registers::interrupt::{gicc_regs, gicd_regs, GICC_IAR, GIC_CTLR};
+
+/// GIC interrupt ID of VideoCore interrupt 0.
+const VC_SPI_BASE: u32 = 96;
+/// Priority of all interrupts (lower value = higher priority).
+const PRIORITY: u32 = 0xA0;
+/// Interrupt IDs from 1020 up are special (1023 = spurious, nothing pending).
+const SPECIAL_IDS: u32 = 1020;
+
+/// Disable all VideoCore interrupts, route them to core 0 and enable the distributor and the
+/// CPU interface of this core.
+pub fn init() {
+    let gicd = gicd_regs();
+    let gicc = gicc_regs();
+    gicd.ctlr.write(GIC_CTLR::ENABLE::CLEAR); // Disable forwarding while configuring
+    for irq in 0..IRQ_COUNT as u32 {
+        let id = VC_SPI_BASE + irq;
+        let (register, bit) = ((id / 32) as usize, 1 << (id % 32));
+        gicd.icenabler[register].set(bit); // Disabled until a handler is registered
+        gicd.icpendr[register].set(bit); // Nothing pending
+        set_byte(&gicd.ipriorityr[(id / 4) as usize], id, PRIORITY);
+        set_byte(&gicd.itargetsr[(id / 4) as usize], id, 0x01); // Core 0
+        let config = &gicd.icfgr[(id / 16) as usize];
+        config.set(config.get() & !(0b10 << ((id % 16) * 2))); // Level-triggered
+    }
+    gicd.ctlr.write(GIC_CTLR::ENABLE::SET);
+    gicc.pmr.set(0xFF); // Do not mask any priority
+    gicc.ctlr.write(GIC_CTLR::ENABLE::SET);
+}
+
+/// Enable VideoCore interrupt `irq`.
+pub fn enable(irq: u32) {
+    let id = VC_SPI_BASE + irq;
+    gicd_regs().isenabler[(id / 32) as usize].set(1 << (id % 32));
+}
+
+/// Disable VideoCore interrupt `irq`.
+pub fn disable(irq: u32) {
+    let id = VC_SPI_BASE + irq;
+    gicd_regs().icenabler[(id / 32) as usize].set(1 << (id % 32));
+}
+
+/// Acknowledge the pending interrupt, pass it to `dispatch` and signal its end.
+/// If more interrupts are pending, the core takes the IRQ exception again right away.
+pub fn handle(dispatch: fn(u32)) {
+    let gicc = gicc_regs();
+    let iar = gicc.iar.get();
+    let id = GICC_IAR::INTERRUPT_ID.read(iar);
+    if id >= SPECIAL_IDS {
+        return; // Spurious: no end of interrupt
+    }
+    if (VC_SPI_BASE..VC_SPI_BASE + IRQ_COUNT as u32).contains(&id) {
+        dispatch(id - VC_SPI_BASE);
+    }
+    gicc.eoir.set(iar);
+}
+
+/// Set the byte of interrupt `id` in a register holding one byte per interrupt.
+fn set_byte(register: &crate::hal::registers::access::ReadWrite, id: u32, value: u32) {
+    let shift = (id % 4) * 8;
+    register.set((register.get() & !(0xFF << shift)) | (value << shift));
+}
@@ -0,0 +1,37 @@
+//! Legacy ARM interrupt controller (BCM2835/BCM2837).
+//!
+//! One enable and one pending bit per VideoCore interrupt, in two banks of 32. There is nothing to
+//! acknowledge: an interrupt stays pending until its source is cleared by the handler.
+
+use crate::hal::registers::interrupt::irq_regs;
+
+/// Disable all VideoCore interrupts.
+pub fn init() {
+    let regs = irq_regs();
+    for bank in &regs.disable_irqs {
+        bank.set(!0);
+    }
+}
+
+/// Enable VideoCore interrupt `irq`.
+pub fn enable(irq: u32) {
+    irq_regs().enable_irqs[(irq / 32) as usize].set(1 << (irq % 32));
+}
+
+/// Disable VideoCore interrupt `irq`.
+pub fn disable(irq: u32) {
+    irq_regs().disable_irqs[(irq / 32) as usize].set(1 << (irq % 32));
+}
+
+/// Pass every pending and enabled interrupt to `dispatch`.
+pub fn handle(dispatch: fn(u32)) {
+    let regs = irq_regs();
+    for bank in 0..2 {
+        let mut pending = regs.irq_pending[bank].get() & regs.enable_irqs[bank].get();
+        while pending != 0 {
+            let bit = pending.trailing_zeros();
+            dispatch(bank as u32 * 32 + bit);
+            pending &= !(1 << bit);
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Exceptions and interrupts.
+//!
+//! [`init`] installs the exception vector table of `boot.S` for the exception level we run at and
+//! sets up the interrupt controller of the board:
+//! - BCM2711 (Pi 4): the GIC-400 ([`gic400`])
+//! - BCM2837 (Pi 3): the legacy ARM interrupt controller ([`legacy`])
+//!
+//! Drivers register a handler per VideoCore peripheral interrupt (see [`irq`]); the numbers are
+//! the same on all boards, each controller translates them. Handlers run in interrupt context
+//! with interrupts masked: they must clear the interrupt source (e.g. GPEDS, UART ICR) and return
+//! quickly.
+//!
+//! # Example
+//! ```rust
+//! use crate::interrupts::{self, irq};
+//!
+//! fn on_uart() { /* read the FIFO, clear the interrupt */ }
+//!
+//! interrupts::init();
+//! interrupts::register(irq::UART, on_uart); // Enables the interrupt in the controller
+//! interrupts::enable();                     // Unmask IRQs on the core
+//! ```
+
+mod gic400;
+mod legacy;
+
+use crate::board::{self, Soc};
+
+/// VideoCore peripheral interrupt numbers (0-63), as used by [`register`].
+pub mod irq {
+    /// Auxiliary peripherals (Mini UART, SPI1, SPI2).
+    pub const AUX: u32 = 29;
+    /// GPIO bank 0 (pins 0-27).
+    pub const GPIO_BANK0: u32 = 49;
+    /// GPIO bank 1 (pins 28-45).
+    pub const GPIO_BANK1: u32 = 50;
+    /// GPIO bank 2 (pins 46-53).
+    pub const GPIO_BANK2: u32 = 51;
+    /// PL011 UARTs (UART0, and UART2-5 on the BCM2711).
+    pub const UART: u32 = 57;
+}
+
+/// Number of VideoCore peripheral interrupts.
+pub const IRQ_COUNT: usize = 64;
+
+/// An interrupt handler.
+pub type Handler = fn();
+
+/// General purpose registers of the interrupted code, as saved by `irq_entry` in `boot.S` (the
+/// start of its frame; q0-q31 follow at offset 192). x19-x28 are not saved: the handler preserves
+/// them like any AAPCS64 function.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct InterruptFrame {
+    /// x0-x18.
+    pub x: [u64; 19],
+    /// Frame pointer (x29).
+    pub fp: u64,
+    /// Link register (x30).
+    pub lr: u64,
+    pub fpcr: u64,
+    pub fpsr: u64,
+}
+
+impl InterruptFrame {
+    /// Bytes `irq_entry` pushes (192 for the registers above, 32 * 16 for q0-q31): the interrupted
+    /// code's SP is the frame address plus this.
+    pub const SIZE: u64 = 192 + 32 * 16;
+}
+
+/// State of the code an IRQ interrupted.
+#[derive(Clone, Copy, Debug)]
+pub struct InterruptedContext {
+    pub frame: InterruptFrame,
+    /// Stack pointer.
+    pub sp: u64,
+    /// Where the code continues (ELR).
+    pub pc: u64,
+    /// Saved program status (SPSR): exception level, DAIF mask bits, condition flags.
+    pub pstate: u64,
+}
+
+/// Install the exception vectors and initialize the interrupt controller (all interrupts disabled).
+/// IRQs stay masked on the core until [`enable`] is called.
+pub fn init() {
+    install_vectors();
+    match board::current().soc {
+        Soc::Bcm2711 => gic400::init(),
+        Soc::Bcm2837 => legacy::init(),
+    }
+}
+
+/// Run `handler` whenever interrupt `irq` fires, and enable it in the interrupt controller.
+pub fn register(irq: u32, handler: Handler) {
+    if irq as usize >= IRQ_COUNT {
+        return; // Invalid interrupt
+    }
+    without_interrupts(|| with_handlers(|handlers| handlers[irq as usize] = Some(handler)));
+    match board::current().soc {
+        Soc::Bcm2711 => gic400::enable(irq),
+        Soc::Bcm2837 => legacy::enable(irq),
+    }
+}
+
+/// Disable interrupt `irq` in the interrupt controller and remove its handler.
+pub fn unregister(irq: u32) {
+    if irq as usize >= IRQ_COUNT {
+        return; // Invalid interrupt
+    }
+    match board::current().soc {
+        Soc::Bcm2711 => gic400::disable(irq),
+        Soc::Bcm2837 => legacy::disable(irq),
+    }
+    without_interrupts(|| with_handlers(|handlers| handlers[irq as usize] = None));
+}
+
+/// Check whether a handler is registered for interrupt `irq`.
+pub fn is_registered(irq: u32) -> bool {
+    with_handlers(|handlers| handlers.get(irq as usize).is_some_and(|handler| handler.is_some()))
+}
+
+/// Unmask IRQs on this core.
+pub fn enable() {
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!("msr daifclr, #2", options(nostack))
+    };
+}
+
+/// Mask IRQs on this core.
+pub fn disable() {
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!("msr daifset, #2", options(nostack))
+    };
+}
+
+/// Run `f` with IRQs masked (a critical section), then restore the previous mask state.
+pub fn without_interrupts<T>(f: impl FnOnce() -> T) -> T {
+    let was_enabled = are_enabled();
+    disable();
+    let result = f();
+    if was_enabled {
+        enable();
+    }
+    result
+}
+
+/// Check whether IRQs are unmasked on this core.
+#[cfg(target_arch = "aarch64")]
+pub fn are_enabled() -> bool {
+    let daif: u64;
+    unsafe { core::arch::asm!("mrs {}, daif", out(reg) daif, options(nomem, nostack)) };
+    daif & (1 << 7) == 0 // DAIF.I
+}
+
+/// Host builds never take interrupts.
+#[cfg(not(target_arch = "aarch64"))]
+pub fn are_enabled() -> bool {
+    false
+}
+
+/// Called by `irq_entry` in `boot.S` for every IRQ exception, with the registers it saved.
+#[no_mangle]
+extern "C" fn irq_handler(frame: *const InterruptFrame) {
+    with_current_frame(|current| *current = frame);
+    match board::current().soc {
+        Soc::Bcm2711 => gic400::handle(dispatch),
+        Soc::Bcm2837 => legacy::handle(dispatch),
+    }
+    with_current_frame(|current| *current = core::ptr::null());
+}
+
+/// Registers of the code interrupted by the IRQ being handled. `None` outside of IRQ handlers.
+pub fn interrupted_context() -> Option<InterruptedContext> {
+    let frame = with_current_frame(|current| *current);
+    // Safety: set by `irq_handler` to the frame on the stack below it, valid until it returns.
+    let saved = unsafe { frame.as_ref() }?;
+    let (_, elr) = exception_syndrome();
+    Some(InterruptedContext {
+        frame: *saved,
+        sp: frame as u64 + InterruptFrame::SIZE,
+        pc: elr,
+        pstate: saved_program_status(),
+    })
+}
+
+/// Run the handler of interrupt `irq`, or disable the interrupt if nobody handles it (otherwise it
+/// would fire again immediately).
+fn dispatch(irq: u32) {
+    match with_handlers(|handlers| handlers[irq as usize]) {
+        Some(handler) => handler(),
+        None => unregister(irq),
+    }
+}
+
+/// Called by `boot.S` for every exception other than an IRQ: report it and stop.
+#[no_mangle]
+extern "C" fn unhandled_exception(index: u64) -> ! {
+    const KINDS: [&str; 4] = ["synchronous exception", "IRQ", "FIQ", "SError"];
+    const ORIGINS: [&str; 4] = ["EL with SP0", "EL with SPx", "lower EL (AArch64)", "lower EL (AArch32)"];
+    let (esr, elr) = exception_syndrome();
+    crate::println!(
+        "Unhandled {} from {}: ESR 0x{:08X}, ELR 0x{:016X}",
+        KINDS[(index % 4) as usize],
+        ORIGINS[(index / 4 % 4) as usize],
+        esr,
+        elr
+    );
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Install `exception_vectors` (from `boot.S`) and route IRQs to the exception level we run at.
+#[cfg(target_arch = "aarch64")]
+fn install_vectors() {
+    extern "C" {
+        static exception_vectors: u8;
+    }
+    let vectors = unsafe { core::ptr::addr_of!(exception_vectors) } as u64;
+    unsafe {
+        match current_el() {
+            // SCR_EL3.IRQ: take physical IRQs at EL3
+            3 => core::arch::asm!(
+                "msr vbar_el3, {vectors}",
+                "mrs {tmp}, scr_el3",
+                "orr {tmp}, {tmp}, #(1 << 1)",
+                "msr scr_el3, {tmp}",
+                "isb",
+                vectors = in(reg) vectors,
+                tmp = out(reg) _,
+                options(nostack),
+            ),
+            // HCR_EL2.IMO: take physical IRQs at EL2 (by default they target EL1 and are never taken)
+            2 => core::arch::asm!(
+                "msr vbar_el2, {vectors}",
+                "mrs {tmp}, hcr_el2",
+                "orr {tmp}, {tmp}, #(1 << 4)",
+                "msr hcr_el2, {tmp}",
+                "isb",
+                vectors = in(reg) vectors,
+                tmp = out(reg) _,
+                options(nostack),
+            ),
+            _ => core::arch::asm!("msr vbar_el1, {}", "isb", in(reg) vectors, options(nostack)),
+        }
+    }
+}
+
+/// Host builds (unit tests) have no exception vectors.
+#[cfg(not(target_arch = "aarch64"))]
+fn install_vectors() {}
+
+/// Exception level the kernel runs at (1-3).
+#[cfg(target_arch = "aarch64")]
+fn current_el() -> u64 {
+    let el: u64;
+    unsafe { core::arch::asm!("mrs {}, CurrentEL", out(reg) el, options(nomem, nostack)) };
+    (el >> 2) & 0b11
+}
+
+/// Exception Syndrome Register and Exception Link Register of the current exception level.
+/// In an interrupt handler, ELR is where the interrupted code will continue.
+#[cfg(target_arch = "aarch64")]
+pub fn exception_syndrome() -> (u64, u64) {
+    let (esr, elr): (u64, u64);
+    unsafe {
+        match current_el() {
+            3 => core::arch::asm!("mrs {}, esr_el3", "mrs {}, elr_el3", out(reg) esr, out(reg) elr, options(nomem, nostack)),
+            2 => core::arch::asm!("mrs {}, esr_el2", "mrs {}, elr_el2", out(reg) esr, out(reg) elr, options(nomem, nostack)),
+            _ => core::arch::asm!("mrs {}, esr_el1", "mrs {}, elr_el1", out(reg) esr, out(reg) elr, options(nomem, nostack)),
+        }
+    }
+    (esr, elr)
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn exception_syndrome() -> (u64, u64) {
+    (0, 0)
+}
+
+/// Saved Program Status Register of the current exception level: PSTATE of the interrupted code.
+#[cfg(target_arch = "aarch64")]
+fn saved_program_status() -> u64 {
+    let spsr: u64;
+    unsafe {
+        match current_el() {
+            3 => core::arch::asm!("mrs {}, spsr_el3", out(reg) spsr, options(nomem, nostack)),
+            2 => core::arch::asm!("mrs {}, spsr_el2", out(reg) spsr, options(nomem, nostack)),
+            _ => core::arch::asm!("mrs {}, spsr_el1", out(reg) spsr, options(nomem, nostack)),
+        }
+    }
+    spsr
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+fn saved_program_status() -> u64 {
+    0
+}
+
+/// Run `f` with the frame of the IRQ being handled (null outside of `irq_handler`).
+#[cfg(not(test))]
+fn with_current_frame<T>(f: impl FnOnce(&mut *const InterruptFrame) -> T) -> T {
+    static mut CURRENT_FRAME: *const InterruptFrame = core::ptr::null();
+    // Safety: single core. Only written by `irq_handler`, which runs with IRQs masked.
+    f(unsafe { &mut *core::ptr::addr_of_mut!(CURRENT_FRAME) })
+}
+
+/// Run `f` with the frame of the IRQ being handled. Host tests get one per test thread.
+#[cfg(test)]
+fn with_current_frame<T>(f: impl FnOnce(&mut *const InterruptFrame) -> T) -> T {
+    extern crate std;
+    std::thread_local! {
+        static CURRENT_FRAME: core::cell::Cell<*const InterruptFrame> = const { core::cell::Cell::new(core::ptr::null()) };
+    }
+    CURRENT_FRAME.with(|current| {
+        let mut frame = current.get();
+        let result = f(&mut frame);
+        current.set(frame);
+        result
+    })
+}
+
+/// Run `f` with the handler table.
+#[cfg(not(test))]
+fn with_handlers<T>(f: impl FnOnce(&mut [Option<Handler>; IRQ_COUNT]) -> T) -> T {
+    static mut HANDLERS: [Option<Handler>; IRQ_COUNT] = [None; IRQ_COUNT];
+    // Safety: single core. The table is only changed with IRQs masked, and the IRQ handler itself
+    // runs with IRQs masked, so the two never overlap.
+    f(unsafe { &mut *core::ptr::addr_of_mut!(HANDLERS) })
+}
+
+/// Run `f` with the handler table. Host tests get one table per test thread.
+#[cfg(test)]
+fn with_handlers<T>(f: impl FnOnce(&mut [Option<Handler>; IRQ_COUNT]) -> T) -> T {
+    extern crate std;
+    std::thread_local! {
+        static HANDLERS: core::cell::RefCell<[Option<Handler>; IRQ_COUNT]> =
+            const { core::cell::RefCell::new([None; IRQ_COUNT]) };
+    }
+    HANDLERS.with(|handlers| f(&mut handlers.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::interrupt::{GICC_REGS_OFFSET, GICD_REGS_OFFSET, IRQ_REGS_OFFSET};
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    static CALLS: AtomicU32 = AtomicU32::new(0);
+
+    fn count_call() {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+        // The frame of the interrupted code is available to the handler
+        assert_eq!(interrupted_context().map(|context| context.frame.x[0]), Some(42));
+    }
+
+    #[test]
+    fn registered_handlers_run_from_the_controller_of_the_board() {
+        sim::reset();
+        init();
+        register(irq::UART, count_call);
+        let frame = InterruptFrame { x: [42; 19], fp: 0, lr: 0, fpcr: 0, fpsr: 0 };
+        match board::current().soc {
+            Soc::Bcm2711 => {
+                // UART = SPI 96 + 57 = 153: ISENABLER4 bit 25, routed to core 0
+                assert_eq!(sim::last_write(GICD_REGS_OFFSET + 0x110), Some(1 << 25));
+                assert_eq!((sim::peek(GICD_REGS_OFFSET + 0x800 + 153 / 4 * 4) >> 8) & 0xFF, 0x01);
+                sim::poke(GICC_REGS_OFFSET + 0x0C, 153); // IAR
+                irq_handler(&frame);
+                assert_eq!(sim::last_write(GICC_REGS_OFFSET + 0x10), Some(153)); // EOIR
+            }
+            Soc::Bcm2837 => {
+                // UART = VideoCore interrupt 57: enable IRQs 2, bit 25
+                assert_eq!(sim::last_write(IRQ_REGS_OFFSET + 0x14), Some(1 << 25));
+                sim::poke(IRQ_REGS_OFFSET + 0x08, 1 << 25); // IRQ pending 2
+                irq_handler(&frame);
+            }
+        }
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        assert!(interrupted_context().is_none()); // Only while an IRQ is handled
+        unregister(irq::UART);
+        assert!(!is_registered(irq::UART));
+    }
+}
@@ -1,20 +1,66 @@
 // Logging module for UART output.
 //
+// The output goes to the console device selected with `console::set` (UART0 by default).
+//
 // NOTE: UART must be initialized (e.g., via `drivers::uart::uart0::init()`) BEFORE using any logging macros (print!/println!).
 // Using logging before UART is initialized will result in lost or invalid output.
 // It is the user's responsibility to ensure correct initialization order (no checks in the code for that).
+//
+// Everything printed is also kept in a history buffer (the last HISTORY_SIZE bytes), so the debug
+// menu can show what happened before it was entered (`dump_history`).
 
 use core::fmt::Write;
 
+use crate::drivers::uart::ring_buffer::RingBuffer;
+use crate::interrupts;
+
+// Bytes of output kept in the history.
+const HISTORY_SIZE: usize = 2048;
+
 pub struct Logger;
 
 impl Write for Logger {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        crate::drivers::uart::uart0::write_string(s);
+        interrupts::without_interrupts(|| {
+            with_history(|history| {
+                for byte in s.bytes() {
+                    if history.is_full() {
+                        history.pop(); // Forget the oldest output
+                    }
+                    history.push(byte);
+                }
+            })
+        });
+        crate::console::get().write_string(s);
         Ok(())
     }
 }
 
+// Write the output history to the console again (written directly, so it is not recorded twice).
+pub fn dump_history() {
+    let console = crate::console::get();
+    interrupts::without_interrupts(|| with_history(|history| history.iter().for_each(|byte| console.write_byte(byte))));
+}
+
+// Run `f` with the output history.
+#[cfg(not(test))]
+fn with_history<T>(f: impl FnOnce(&mut RingBuffer<HISTORY_SIZE>) -> T) -> T {
+    static mut HISTORY: RingBuffer<HISTORY_SIZE> = RingBuffer::new();
+    // Safety: single core, and the history is only used with IRQs masked.
+    f(unsafe { &mut *core::ptr::addr_of_mut!(HISTORY) })
+}
+
+// Run `f` with the output history. Host tests get one history per test thread.
+#[cfg(test)]
+fn with_history<T>(f: impl FnOnce(&mut RingBuffer<HISTORY_SIZE>) -> T) -> T {
+    extern crate std;
+    std::thread_local! {
+        static HISTORY: core::cell::RefCell<RingBuffer<HISTORY_SIZE>> =
+            const { core::cell::RefCell::new(RingBuffer::new()) };
+    }
+    HISTORY.with(|history| f(&mut history.borrow_mut()))
+}
+
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => {{
@@ -31,3 +77,25 @@ macro_rules! println {
         $crate::print!("\r\n");
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mmio::sim;
+    use crate::hal::registers::uart::PL011_UART_REGS_OFFSET;
+
+    const DR: usize = PL011_UART_REGS_OFFSET;
+
+    #[test]
+    fn the_history_keeps_the_latest_output() {
+        sim::reset();
+        for line in 0..300 {
+            crate::println!("line {:03}", line); // 10 bytes each, 3000 in total
+        }
+        let writes = sim::writes(DR).len();
+        dump_history();
+        let dumped: std::vec::Vec<u8> = sim::writes(DR)[writes..].iter().map(|&byte| byte as u8).collect();
+        assert_eq!(dumped.len(), HISTORY_SIZE);
+        assert!(dumped.ends_with(b"line 299\r\n"));
+    }
+}
@@ -1,50 +1,82 @@
-#![no_std]
-#![no_main]
+// The kernel is `no_std`/`no_main`. Unit tests (`make test`) are built for the host instead,
+// with the standard library and the test harness, and run against the simulated MMIO backend.
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 
 use core::panic::PanicInfo;
+mod board;
+mod console;
+mod debug_menu;
 mod drivers;
 mod hal;
+mod interrupts;
 mod log;
 
 use drivers::gpio::GpioPin;
+use drivers::peripherals::Peripherals;
+use drivers::uart::UartConfig;
 use log::*;
 
 
 
 // This is what your assembly boot.S calls
+#[cfg(not(test))] // The test harness brings its own `main`
 #[no_mangle] // Ensure the function name is not mangled by the compiler
 // this is the section main of that the assembly code will jump to
 pub extern "C" fn main() -> ! {
+    // Find out which board we run on first: every driver depends on its peripheral base and clocks
+    board::init();
+
     // Initialize UART first otherwise logging will not work
-    drivers::uart::uart0::init();
+    // (nothing else owns GPIO14/15 yet, so this cannot fail)
+    let peripherals = Peripherals::take().unwrap();
+    drivers::uart::uart0::init(peripherals.uart0, &UartConfig::default()).unwrap();
+    // print!/println! go to the console device (e.g. `&MiniUartSerial` if UART0 drives Bluetooth)
+    console::set(&drivers::uart::Uart0Serial);
+
+    // Install the exception vectors and set up the interrupt controller, then unmask IRQs.
+    // UART0 switches to interrupt mode: bytes received during the delay below are buffered.
+    // A break on the console opens the debug menu.
+    interrupts::init();
+    drivers::uart::uart0::enable_interrupts();
+    debug_menu::install(); // The console is UART0, so this cannot fail
+    interrupts::enable();
     
     // Send a test message
-println!("Hello from Raspberry Pi 4 UART!");
-    println!("UART is working!");
-    println!("Send any character to see it echoed back!");
+    let board = board::current();
+    println!("Hello from {}!", board.name);
+    println!("UART is working! Peripherals at 0x{:08X}", board.peripheral_base);
+    if board.revision != 0 {
+        println!("Board revision 0x{:06X}, {} MB RAM", board.revision, board.memory.ram_size / (1024 * 1024));
+    }
+    println!("Send any character to see it echoed back! (Send a break for the debug menu)");
     
-    // Example: Blink ACT LED (GPIO 42) to confirm kernel is running
-    let act_led = GpioPin::new(42);
-    act_led.set_output();
-    let mut led_on = false;
+    // Example: Blink ACT LED (GPIO 42 on the Pi 4) to confirm kernel is running
+    // (boards without a GPIO-driven ACT LED, like the Pi 3B, simply skip the blinking)
+    let act_led = board.act_led_pin.and_then(|pin| GpioPin::new(pin, "act_led").ok());
+    if let Some(led) = &act_led {
+        led.set_output();
+    }
+    drivers::pins::report();
     
     let mut counter = 0u32;
     loop {
         // Send counter via UART
         println!("Loop count: {}", counter);
         
-        // Check for incoming UART data and echo it
-        if let Some(received_byte) = drivers::uart::uart0::read_byte() {
+        // Echo everything received since the last iteration
+        while let Some(received_byte) = drivers::uart::uart0::read_byte() {
             print!("Received: '{}' (0x{:02X})\r\n", received_byte as char, received_byte);
         }
         
-        // Blink ACT LED (toggle logic in main)
-        if led_on {
-            act_led.set_low();
-        } else {
-            act_led.set_high();
+        // Blink ACT LED (toggle based on the level read back from the pin)
+        if let Some(led) = &act_led {
+            if led.is_set_high() {
+                led.set_low();
+            } else {
+                led.set_high();
+            }
         }
-        led_on = !led_on;
         
         // Simple delay
         for _ in 0..1000000 {
@@ -55,6 +87,7 @@ println!("Hello from Raspberry Pi 4 UART!");
     }
 }
 
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
     loop {}